@@ -0,0 +1,27 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    cursor_controller::CursorController, editor_rows::EditorRows,
+    syntax_highlighting::SyntaxHighlight,
+};
+
+/// The per-file state of a buffer that isn't currently shown.
+pub struct Buffer {
+    pub editor_rows: EditorRows,
+    pub cursor_controller: CursorController,
+    pub dirty: u64,
+    pub syntax_highlight: Option<Box<dyn SyntaxHighlight>>,
+    pub bookmarks: BTreeSet<usize>,
+}
+
+impl Buffer {
+    pub fn new(win_size: (usize, usize)) -> Self {
+        Self {
+            editor_rows: EditorRows::new(),
+            cursor_controller: CursorController::new(win_size),
+            dirty: 0,
+            syntax_highlight: None,
+            bookmarks: BTreeSet::new(),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::{env, fs, io, path::PathBuf, time::Duration};
+
+use crossterm::cursor::CursorShape;
+
+use crate::{
+    keymap::{Action, Keymap},
+    output::{Bell, CursorShapes},
+    COPY_COMMAND, LARGE_FILE_BYTES, MAX_LINE_LENGTH, QUIT_TIMES, RULER_COLUMN, SCROLLOFF,
+    STATUS_MESSAGE_TIMEOUT, TIMESTAMP_FORMAT, WRAP_COLUMN,
+};
+
+/// User settings read from `$XDG_CONFIG_HOME/pound/config` (or `~/.config/pound/config`).
+///
+/// The file holds `key = value` lines; blank lines and lines starting with `#` are ignored.
+/// Lines like `bind.find = ctrl-e, f3` replace the keys bound to an action; an empty
+/// list leaves it unbound.
+pub struct Config {
+    /// How many extra Ctrl-Q presses quitting with unsaved changes takes.
+    pub quit_times: u8,
+    /// `bell = none | audible | visual` picks how failed actions are signalled.
+    pub bell: Bell,
+    /// Files larger than this many bytes (set as `large_file_mb`) ask before opening.
+    pub large_file_bytes: u64,
+    /// `cursor.insert`, `cursor.overwrite` and `cursor.read_only` each take
+    /// `block | underline | bar`.
+    pub cursor_shapes: CursorShapes,
+    /// Shell command that `copy_command = pbcopy` makes the system clipboard copy pipe to.
+    pub copy_command: String,
+    /// How long status bar messages stay visible, set in seconds as `message_timeout_secs`.
+    pub status_message_timeout: Duration,
+    /// Rows of context kept above and below the cursor when scrolling.
+    pub scrolloff: usize,
+    /// Column the ruler is drawn at when it's turned on.
+    pub ruler_column: usize,
+    /// Length past which rows are tinted when long lines are marked.
+    pub max_line_length: usize,
+    /// Column that paragraphs are reflowed to fit within.
+    pub wrap_column: usize,
+    /// `strftime` pattern used when inserting a timestamp.
+    pub timestamp_format: String,
+    pub keymap: Keymap,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            quit_times: QUIT_TIMES,
+            bell: Bell::None,
+            large_file_bytes: LARGE_FILE_BYTES,
+            cursor_shapes: CursorShapes::new(),
+            copy_command: COPY_COMMAND.into(),
+            status_message_timeout: STATUS_MESSAGE_TIMEOUT,
+            scrolloff: SCROLLOFF,
+            ruler_column: RULER_COLUMN,
+            max_line_length: MAX_LINE_LENGTH,
+            wrap_column: WRAP_COLUMN,
+            timestamp_format: TIMESTAMP_FORMAT.into(),
+            keymap: Keymap::new(),
+        }
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("pound").join("config"))
+    }
+
+    /// Loads the config file, falling back to the defaults when there isn't one.
+    pub fn load() -> Result<Self, String> {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(Self::new()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(err) => Err(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut config = Self::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| format!("line {}: expected key = value", number + 1))?;
+            match key {
+                "quit_times" => {
+                    config.quit_times = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid quit_times", number + 1))?
+                }
+                "large_file_mb" => {
+                    config.large_file_bytes = value
+                        .parse::<u64>()
+                        .map_err(|_| format!("line {}: invalid large_file_mb", number + 1))?
+                        * 1024
+                        * 1024
+                }
+                "copy_command" => config.copy_command = value.into(),
+                "timestamp_format" => config.timestamp_format = value.into(),
+                "wrap_column" => {
+                    config.wrap_column = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid wrap_column", number + 1))?
+                }
+                "max_line_length" => {
+                    config.max_line_length = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid max_line_length", number + 1))?
+                }
+                "ruler_column" => {
+                    config.ruler_column = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid ruler_column", number + 1))?
+                }
+                "scrolloff" => {
+                    config.scrolloff = value
+                        .parse()
+                        .map_err(|_| format!("line {}: invalid scrolloff", number + 1))?
+                }
+                "message_timeout_secs" => {
+                    config.status_message_timeout =
+                        Duration::from_secs(value.parse().map_err(|_| {
+                            format!("line {}: invalid message_timeout_secs", number + 1)
+                        })?)
+                }
+                "bell" => {
+                    config.bell = match value {
+                        "none" => Bell::None,
+                        "audible" => Bell::Audible,
+                        "visual" => Bell::Visual,
+                        _ => return Err(format!("line {}: invalid bell {}", number + 1, value)),
+                    }
+                }
+                "cursor.insert" | "cursor.overwrite" | "cursor.read_only" => {
+                    let shape = match value {
+                        "block" => CursorShape::Block,
+                        "underline" => CursorShape::UnderScore,
+                        "bar" => CursorShape::Line,
+                        _ => {
+                            return Err(format!(
+                                "line {}: invalid cursor shape {}",
+                                number + 1,
+                                value
+                            ))
+                        }
+                    };
+                    match key {
+                        "cursor.insert" => config.cursor_shapes.insert = shape,
+                        "cursor.overwrite" => config.cursor_shapes.overwrite = shape,
+                        _ => config.cursor_shapes.read_only = shape,
+                    }
+                }
+                _ => match key.strip_prefix("bind.") {
+                    Some(name) => {
+                        let action = Action::from_name(name).ok_or_else(|| {
+                            format!("line {}: unknown action {}", number + 1, name)
+                        })?;
+                        let keys = value
+                            .split(',')
+                            .filter(|key| !key.trim().is_empty())
+                            .map(|key| {
+                                Keymap::parse_key(key).ok_or_else(|| {
+                                    format!("line {}: invalid key {}", number + 1, key.trim())
+                                })
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        config.keymap.bind(action, &keys);
+                    }
+                    None => return Err(format!("line {}: unknown setting {}", number + 1, key)),
+                },
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+
+    #[test]
+    fn reads_message_timeout() {
+        let config = Config::parse("message_timeout_secs = 12\n").unwrap();
+        assert_eq!(config.status_message_timeout, Duration::from_secs(12));
+        assert_eq!(
+            Config::parse("\nmessage_timeout_secs = soon\n").err(),
+            Some("line 2: invalid message_timeout_secs".into())
+        );
+    }
+
+    #[test]
+    fn reads_scrolloff() {
+        assert_eq!(Config::parse("scrolloff = 4").unwrap().scrolloff, 4);
+        assert_eq!(Config::new().scrolloff, SCROLLOFF);
+        assert!(Config::parse("scrolloff = -1").is_err());
+    }
+
+    #[test]
+    fn reads_ruler_column() {
+        assert_eq!(
+            Config::parse("ruler_column = 100").unwrap().ruler_column,
+            100
+        );
+        assert_eq!(Config::new().ruler_column, RULER_COLUMN);
+    }
+
+    #[test]
+    fn reads_max_line_length() {
+        assert_eq!(
+            Config::parse("max_line_length = 72")
+                .unwrap()
+                .max_line_length,
+            72
+        );
+        assert_eq!(Config::new().max_line_length, MAX_LINE_LENGTH);
+    }
+
+    #[test]
+    fn reads_wrap_column() {
+        assert_eq!(Config::parse("wrap_column = 60").unwrap().wrap_column, 60);
+        assert!(Config::parse("wrap_column = wide").is_err());
+    }
+
+    #[test]
+    fn reads_timestamp_format() {
+        let config = Config::parse("timestamp_format = %d/%m/%Y %H:%M").unwrap();
+        assert_eq!(config.timestamp_format, "%d/%m/%Y %H:%M");
+    }
+
+    #[test]
+    fn moves_find_to_another_key() {
+        let config = Config::parse("bind.find = ctrl-e, shift-f3").unwrap();
+        let ctrl = |ch| KeyEvent {
+            code: KeyCode::Char(ch),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        assert_eq!(config.keymap.action(ctrl('e')), Some(Action::Find));
+        assert_eq!(config.keymap.action(ctrl('f')), None);
+        assert_eq!(
+            config.keymap.describe(Action::Find).as_deref(),
+            Some("Ctrl-E / Shift-F3")
+        );
+        assert_eq!(
+            Config::parse("bind.find = ctrl-hyper-e").err(),
+            Some("line 1: invalid key ctrl-hyper-e".into())
+        );
+    }
+}
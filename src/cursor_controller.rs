@@ -2,7 +2,9 @@ use std::cmp::{self, Ordering};
 
 use crossterm::event::KeyCode;
 
-use crate::{editor_rows::EditorRows, row::Row, TAB_STOP};
+use crate::{
+    editor_rows::EditorRows, row::Row, search_index::SearchDirection, SCROLLOFF, SCROLL_LINES,
+};
 
 #[derive(Copy, Clone)]
 pub struct CursorController {
@@ -13,6 +15,12 @@ pub struct CursorController {
     pub row_offset: usize,
     pub column_offset: usize,
     pub render_x: usize,
+    /// Number of rows of context kept visible above and below the cursor.
+    pub scrolloff: usize,
+    /// The `(x, y)` position where the selection started, if one is active.
+    pub selection_anchor: Option<(usize, usize)>,
+    /// Whether long rows wrap onto several screen lines instead of scrolling sideways.
+    pub soft_wrap: bool,
 }
 
 impl CursorController {
@@ -25,80 +33,450 @@ impl CursorController {
             row_offset: 0,
             column_offset: 0,
             render_x: 0,
+            scrolloff: SCROLLOFF,
+            selection_anchor: None,
+            soft_wrap: false,
         }
     }
 
+    /// Render positions where each screen line of the row at `at` starts.
+    pub fn wrap_starts(&self, at: usize, editor_rows: &EditorRows) -> Vec<usize> {
+        if self.soft_wrap && at < editor_rows.number_of_rows() {
+            editor_rows
+                .get_editor_row(at)
+                .wrap_starts(self.screen_columns)
+        } else {
+            vec![0]
+        }
+    }
+
+    /// Index of the screen line of a wrapped row that holds `render_x`.
+    fn wrap_line(starts: &[usize], render_x: usize) -> usize {
+        starts
+            .iter()
+            .rposition(|start| *start <= render_x)
+            .unwrap_or(0)
+    }
+
+    /// Returns where the cursor is drawn, relative to the top left of the text area.
+    pub fn screen_position(&self, editor_rows: &EditorRows) -> (usize, usize) {
+        let mut screen_y = 0;
+        let mut at = self.row_offset;
+        while at < self.cursor_y {
+            screen_y += self.wrap_starts(at, editor_rows).len();
+            at = editor_rows.next_visible_row(at);
+        }
+        if !self.soft_wrap {
+            return (self.render_x - self.column_offset, screen_y);
+        }
+        let starts = self.wrap_starts(self.cursor_y, editor_rows);
+        let line = Self::wrap_line(&starts, self.render_x);
+        (
+            cmp::min(
+                self.render_x - starts[line],
+                self.screen_columns.saturating_sub(1),
+            ),
+            screen_y + line,
+        )
+    }
+
+    /// Moves the cursor to the render column `render_x` of the row at `at`, without
+    /// going past the end of the screen line that starts at `line_start`.
+    fn move_to_render_x(
+        &mut self,
+        at: usize,
+        line_start: usize,
+        render_x: usize,
+        editor_rows: &EditorRows,
+    ) {
+        let starts = self.wrap_starts(at, editor_rows);
+        let row = editor_rows.get_editor_row(at);
+        let line_end = starts
+            .iter()
+            .find(|start| **start > line_start)
+            .map_or(usize::MAX, |start| start - 1);
+        let render_x = cmp::min(line_start + render_x, line_end);
+        self.cursor_y = at;
+        self.cursor_x = if render_x >= row.render.chars().count() {
+            row.char_count()
+        } else {
+            row.get_row_content_x(render_x)
+        };
+    }
+
     pub fn get_render_x(&self, row: &Row) -> usize {
-        row.row_content
-            .chars()
-            .take(self.cursor_x)
-            .fold(0, |render_x, c| {
-                if c == '\t' {
-                    render_x + (TAB_STOP - 1) - (render_x % TAB_STOP) + 1
-                } else {
-                    render_x + 1
-                }
-            })
+        row.get_render_x(self.cursor_x)
+    }
+
+    pub fn start_selection(&mut self) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some((self.cursor_x, self.cursor_y));
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+    }
+
+    /// Returns the `(x, y)` start and end of the selection, ordered so that the
+    /// start comes first in the buffer. Empty selections are treated as none.
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let (anchor_x, anchor_y) = self.selection_anchor?;
+        let anchor = (anchor_y, anchor_x);
+        let cursor = (self.cursor_y, self.cursor_x);
+        match anchor.cmp(&cursor) {
+            Ordering::Less => Some(((anchor_x, anchor_y), (self.cursor_x, self.cursor_y))),
+            Ordering::Greater => Some(((self.cursor_x, self.cursor_y), (anchor_x, anchor_y))),
+            Ordering::Equal => None,
+        }
+    }
+
+    /// Returns the selected part of the row at `at` as a range of render positions.
+    pub fn selected_render_range(&self, at: usize, row: &Row) -> Option<(usize, usize)> {
+        let ((start_x, start_y), (end_x, end_y)) = self.selection()?;
+        if at < start_y || at > end_y {
+            return None;
+        }
+        let start = if at == start_y {
+            row.get_render_x(start_x)
+        } else {
+            0
+        };
+        let end = if at == end_y {
+            row.get_render_x(end_x)
+        } else {
+            row.render.len()
+        };
+        Some((start, end))
+    }
+
+    /// The scrolloff margin, capped so the top and bottom margins never overlap.
+    fn effective_scrolloff(&self) -> usize {
+        cmp::min(self.scrolloff, self.screen_rows.saturating_sub(1) / 2)
     }
 
     pub fn scroll(&mut self, editor_rows: &EditorRows) {
+        if let Some(start) = editor_rows.fold_containing(self.cursor_y) {
+            self.cursor_y = start;
+            self.cursor_x = cmp::min(
+                self.cursor_x,
+                editor_rows.get_editor_row(start).char_count(),
+            );
+        }
         self.render_x = 0;
         if self.cursor_y < editor_rows.number_of_rows() {
             self.render_x = self.get_render_x(editor_rows.get_editor_row(self.cursor_y));
         }
-        self.row_offset = cmp::min(self.row_offset, self.cursor_y);
-        if self.cursor_y >= self.row_offset + self.screen_rows {
-            self.row_offset = self.cursor_y - self.screen_rows + 1;
+        let scrolloff = self.effective_scrolloff();
+        self.row_offset = editor_rows
+            .fold_containing(self.row_offset)
+            .unwrap_or(self.row_offset);
+        self.row_offset = cmp::min(
+            self.row_offset,
+            editor_rows.visible_row_before(self.cursor_y, scrolloff),
+        );
+        if self.soft_wrap {
+            // Walk up from the cursor's screen line to find the lowest offset that
+            // still shows it.
+            let starts = self.wrap_starts(self.cursor_y, editor_rows);
+            let mut lines = Self::wrap_line(&starts, self.render_x) + 1;
+            let mut lowest = self.cursor_y;
+            while lowest > 0 {
+                let previous = editor_rows.previous_visible_row(lowest);
+                lines += self.wrap_starts(previous, editor_rows).len();
+                if lines > self.screen_rows {
+                    break;
+                }
+                lowest = previous;
+            }
+            self.row_offset = cmp::max(self.row_offset, lowest);
+            self.column_offset = 0;
+            return;
         }
+        let bottom = editor_rows.visible_row_after(self.cursor_y, scrolloff);
+        self.row_offset = cmp::max(
+            self.row_offset,
+            editor_rows.visible_row_before(bottom, self.screen_rows.saturating_sub(1)),
+        );
         self.column_offset = cmp::min(self.column_offset, self.render_x);
         if self.render_x >= self.column_offset + self.screen_columns {
             self.column_offset = self.render_x - self.screen_columns + 1;
         }
     }
 
+    /// Places the cursor under a screen cell, e.g. one that was clicked, mapping the
+    /// column back through tab expansion to a position in `row_content`.
+    pub fn move_to_screen_position(&mut self, column: usize, row: usize, editor_rows: &EditorRows) {
+        let number_of_rows = editor_rows.number_of_rows();
+        if row >= self.screen_rows || number_of_rows == 0 {
+            return;
+        }
+        if self.soft_wrap {
+            let mut at = self.row_offset;
+            let mut line = row;
+            loop {
+                let starts = self.wrap_starts(at, editor_rows);
+                let next = editor_rows.next_visible_row(at);
+                if line < starts.len() || next >= number_of_rows {
+                    let line_start = starts[cmp::min(line, starts.len() - 1)];
+                    self.move_to_render_x(at, line_start, column, editor_rows);
+                    return;
+                }
+                line -= starts.len();
+                at = next;
+            }
+        }
+        self.cursor_y = editor_rows.visible_row_after(self.row_offset, row);
+        let editor_row = editor_rows.get_editor_row(self.cursor_y);
+        let render_x = column + self.column_offset;
+        self.cursor_x = if render_x >= editor_row.render.len() {
+            editor_row.char_count()
+        } else {
+            editor_row.get_row_content_x(render_x)
+        };
+    }
+
+    /// Scrolls the view by `SCROLL_LINES`, only dragging the cursor along when it
+    /// would otherwise end up off screen.
+    pub fn scroll_view(&mut self, direction: SearchDirection, editor_rows: &EditorRows) {
+        let number_of_rows = editor_rows.number_of_rows();
+        if number_of_rows == 0 {
+            return;
+        }
+        self.row_offset = match direction {
+            SearchDirection::Backward => {
+                editor_rows.visible_row_before(self.row_offset, SCROLL_LINES)
+            }
+            SearchDirection::Forward => {
+                editor_rows.visible_row_after(self.row_offset, SCROLL_LINES)
+            }
+        };
+        let scrolloff = self.effective_scrolloff();
+        let last_visible = editor_rows.visible_row_after(
+            self.row_offset,
+            self.screen_rows.saturating_sub(1 + scrolloff),
+        );
+        let first_visible = if self.row_offset == 0 {
+            0
+        } else {
+            cmp::min(
+                editor_rows.visible_row_after(self.row_offset, scrolloff),
+                last_visible,
+            )
+        };
+        self.cursor_y = self.cursor_y.clamp(first_visible, last_visible);
+        self.cursor_x = cmp::min(
+            self.cursor_x,
+            editor_rows.get_editor_row(self.cursor_y).char_count(),
+        );
+    }
+
+    /// Moves both the view and the cursor by half a screen, keeping the cursor as
+    /// close as possible to its current render column.
+    pub fn scroll_half_page(&mut self, direction: SearchDirection, editor_rows: &EditorRows) {
+        let number_of_rows = editor_rows.number_of_rows();
+        if number_of_rows == 0 {
+            return;
+        }
+        let half_page = cmp::max(self.screen_rows / 2, 1);
+        match direction {
+            SearchDirection::Backward => {
+                self.row_offset = editor_rows.visible_row_before(self.row_offset, half_page);
+                self.cursor_y = editor_rows.visible_row_before(self.cursor_y, half_page);
+            }
+            SearchDirection::Forward => {
+                self.row_offset = editor_rows.visible_row_after(self.row_offset, half_page);
+                self.cursor_y = editor_rows.visible_row_after(self.cursor_y, half_page);
+            }
+        }
+        let row = editor_rows.get_editor_row(self.cursor_y);
+        self.cursor_x = if self.render_x >= row.render.len() {
+            row.char_count()
+        } else {
+            row.get_row_content_x(self.render_x)
+        };
+    }
+
+    /// Moves up or down one screen line of a soft-wrapped buffer, keeping the column
+    /// within the line.
+    fn move_screen_line(&mut self, direction: KeyCode, editor_rows: &EditorRows) {
+        let render_x = self.get_render_x(editor_rows.get_editor_row(self.cursor_y));
+        let starts = self.wrap_starts(self.cursor_y, editor_rows);
+        let line = Self::wrap_line(&starts, render_x);
+        let column = render_x - starts[line];
+        match direction {
+            KeyCode::Up if line > 0 => {
+                self.move_to_render_x(self.cursor_y, starts[line - 1], column, editor_rows)
+            }
+            KeyCode::Up if self.cursor_y > 0 => {
+                let previous = editor_rows.previous_visible_row(self.cursor_y);
+                let line_start = *self.wrap_starts(previous, editor_rows).last().unwrap();
+                self.move_to_render_x(previous, line_start, column, editor_rows)
+            }
+            KeyCode::Down if line + 1 < starts.len() => {
+                self.move_to_render_x(self.cursor_y, starts[line + 1], column, editor_rows)
+            }
+            KeyCode::Down => {
+                let next = editor_rows.next_visible_row(self.cursor_y);
+                if next < editor_rows.number_of_rows() {
+                    self.move_to_render_x(next, 0, column, editor_rows)
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The `(x, y)` position of the next word boundary from the cursor: past any
+    /// punctuation and whitespace, then past the run of word characters. At either
+    /// edge of a row the boundary is the edge of the neighbouring row instead.
+    pub fn word_boundary(
+        &self,
+        direction: SearchDirection,
+        editor_rows: &EditorRows,
+    ) -> (usize, usize) {
+        let (x, y) = (self.cursor_x, self.cursor_y);
+        let chars = match editor_rows.row_contents.get(y) {
+            Some(row) => row.row_content.chars().collect::<Vec<char>>(),
+            None => return (x, y),
+        };
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        match direction {
+            SearchDirection::Forward if x >= chars.len() => {
+                if y + 1 < editor_rows.number_of_rows() {
+                    (0, y + 1)
+                } else {
+                    (x, y)
+                }
+            }
+            SearchDirection::Forward => {
+                let rest = &chars[x..];
+                let gap = rest.iter().take_while(|c| !is_word(c)).count();
+                let word = rest[gap..].iter().take_while(|c| is_word(c)).count();
+                (x + gap + word, y)
+            }
+            SearchDirection::Backward if x == 0 => {
+                if y > 0 {
+                    (editor_rows.get_editor_row(y - 1).char_count(), y - 1)
+                } else {
+                    (x, y)
+                }
+            }
+            SearchDirection::Backward => {
+                let before = &chars[..x];
+                let gap = before.iter().rev().take_while(|c| !is_word(c)).count();
+                let word = before[..x - gap]
+                    .iter()
+                    .rev()
+                    .take_while(|c| is_word(c))
+                    .count();
+                (x - gap - word, y)
+            }
+        }
+    }
+
     pub fn move_cursor(&mut self, direction: KeyCode, editor_rows: &EditorRows) {
         let number_of_rows = editor_rows.number_of_rows();
 
         match direction {
+            KeyCode::Up | KeyCode::Down if self.soft_wrap && self.cursor_y < number_of_rows => {
+                self.move_screen_line(direction, editor_rows);
+            }
             KeyCode::Up => {
-                self.cursor_y = self.cursor_y.saturating_sub(1);
+                self.cursor_y = editor_rows.previous_visible_row(self.cursor_y);
             }
             KeyCode::Left => {
                 if self.cursor_x != 0 {
                     self.cursor_x -= 1;
                 } else if self.cursor_y > 0 {
-                    self.cursor_y -= 1;
-                    self.cursor_x = editor_rows.get_row(self.cursor_y).len();
+                    self.cursor_y = editor_rows.previous_visible_row(self.cursor_y);
+                    self.cursor_x = editor_rows.get_editor_row(self.cursor_y).char_count();
                 }
             }
             KeyCode::Down => {
-                if self.cursor_y < number_of_rows - 1 {
-                    self.cursor_y += 1;
-                }
+                self.cursor_y = editor_rows.visible_row_after(self.cursor_y, 1);
             }
-            KeyCode::Right => match self.cursor_x.cmp(&editor_rows.get_row(self.cursor_y).len()) {
+            KeyCode::Right => match self
+                .cursor_x
+                .cmp(&editor_rows.get_editor_row(self.cursor_y).char_count())
+            {
                 Ordering::Less => self.cursor_x += 1,
-                Ordering::Equal => {
-                    if self.cursor_y < number_of_rows - 1 {
-                        self.cursor_y += 1;
-                        self.cursor_x = 0
-                    }
+                Ordering::Equal if editor_rows.next_visible_row(self.cursor_y) < number_of_rows => {
+                    self.cursor_y = editor_rows.next_visible_row(self.cursor_y);
+                    self.cursor_x = 0
                 }
                 _ => {}
             },
             KeyCode::End => {
                 if self.cursor_y < number_of_rows {
-                    self.cursor_x = editor_rows.get_row(self.cursor_y).len();
+                    self.cursor_x = editor_rows.get_editor_row(self.cursor_y).char_count();
                 }
             }
             KeyCode::Home => self.cursor_x = 0,
             _ => unimplemented!(),
         }
         let row_len = if self.cursor_y < number_of_rows {
-            editor_rows.get_row(self.cursor_y).len()
+            editor_rows.get_editor_row(self.cursor_y).char_count()
         } else {
             0
         };
         self.cursor_x = cmp::min(self.cursor_x, row_len);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn end_lands_after_last_multibyte_character() {
+        let rows = EditorRows::from_str("héllo wörld\n", &mut None);
+        let mut cursor = CursorController::new((80, 24));
+        cursor.move_cursor(KeyCode::End, &rows);
+        assert_eq!(cursor.cursor_x, 11);
+        cursor.move_cursor(KeyCode::Left, &rows);
+        assert_eq!(cursor.cursor_x, 10);
+    }
+
+    #[test]
+    fn left_at_row_start_goes_to_previous_row_end() {
+        let rows = EditorRows::from_str("né\nx\n", &mut None);
+        let mut cursor = CursorController::new((80, 24));
+        cursor.cursor_y = 1;
+        cursor.move_cursor(KeyCode::Left, &rows);
+        assert_eq!((cursor.cursor_x, cursor.cursor_y), (2, 0));
+    }
+
+    #[test]
+    fn scrolling_the_view_drags_the_cursor_only_when_it_leaves_the_screen() {
+        let rows = EditorRows::from_str(&"line\n".repeat(20), &mut None);
+        let mut cursor = CursorController::new((80, 5));
+        cursor.cursor_y = 4;
+        cursor.scroll_view(SearchDirection::Forward, &rows);
+        assert_eq!((cursor.row_offset, cursor.cursor_y), (SCROLL_LINES, 4));
+        cursor.scroll_view(SearchDirection::Forward, &rows);
+        assert_eq!(
+            (cursor.row_offset, cursor.cursor_y),
+            (2 * SCROLL_LINES, 2 * SCROLL_LINES)
+        );
+        cursor.scroll_view(SearchDirection::Backward, &rows);
+        assert_eq!(cursor.row_offset, SCROLL_LINES);
+    }
+
+    #[test]
+    fn half_page_scroll_keeps_the_render_column() {
+        let rows = EditorRows::from_str("\tab\n".repeat(20).as_str(), &mut None);
+        let mut cursor = CursorController::new((80, 10));
+        cursor.move_cursor(KeyCode::End, &rows);
+        cursor.scroll(&rows);
+        cursor.scroll_half_page(SearchDirection::Forward, &rows);
+        assert_eq!(
+            (cursor.cursor_x, cursor.cursor_y, cursor.row_offset),
+            (3, 5, 5)
+        );
+        cursor.scroll_half_page(SearchDirection::Backward, &rows);
+        assert_eq!(
+            (cursor.cursor_x, cursor.cursor_y, cursor.row_offset),
+            (3, 0, 0)
+        );
+    }
+}
@@ -1,33 +1,311 @@
-use std::{cmp, path::PathBuf};
+use std::{cmp, path::PathBuf, time::Instant};
 
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 
-use crate::{editor_rows::FileType, output::Output, reader::Reader, QUIT_TIMES};
+use crate::{
+    config::Config,
+    editor_rows::FileType,
+    keymap::Action,
+    output::{EditCommand, Output},
+    reader::Reader,
+    search_index::SearchDirection,
+};
+
+/// Named actions offered by the command palette.
+const COMMANDS: [(&str, Action); 57] = [
+    ("Save", Action::Save),
+    ("Save As", Action::SaveAs),
+    ("Save All", Action::SaveAll),
+    ("Find", Action::Find),
+    ("Replace", Action::Replace),
+    ("Go to Line", Action::GoToLine),
+    ("Go to Percent", Action::GoToPercent),
+    ("Open File", Action::OpenFile),
+    ("Open File Under Cursor", Action::OpenFileUnderCursor),
+    ("Quick Open", Action::QuickOpen),
+    ("New Buffer", Action::NewBuffer),
+    ("Close Buffer", Action::CloseBuffer),
+    ("Next Buffer", Action::NextBuffer),
+    ("Previous Buffer", Action::PreviousBuffer),
+    ("Copy", Action::Copy),
+    ("Copy to System Clipboard", Action::CopyToCommand),
+    ("Filter Through Command", Action::FilterThroughCommand),
+    ("Paste", Action::Paste),
+    ("Join Lines", Action::JoinLines),
+    ("Add Cursor at Next Match", Action::AddCursorAtNextMatch),
+    ("Add Cursor Below", Action::AddCursorBelow),
+    ("Clear Cursors", Action::ClearCursors),
+    ("Delete Previous Word", Action::DeletePreviousWord),
+    ("Delete Next Word", Action::DeleteNextWord),
+    ("Delete to Line End", Action::DeleteToLineEnd),
+    ("Delete to Line Start", Action::DeleteToLineStart),
+    ("Toggle Bookmark", Action::ToggleBookmark),
+    ("Next Bookmark", Action::NextBookmark),
+    ("Previous Bookmark", Action::PreviousBookmark),
+    ("Toggle Fold", Action::ToggleFold),
+    ("Cycle Case", Action::CycleCase),
+    ("Sort Lines", Action::SortLines),
+    ("Remove Duplicate Lines", Action::RemoveDuplicateLines),
+    ("Toggle Long Lines", Action::ToggleLongLines),
+    (
+        "Toggle Trailing Whitespace",
+        Action::ToggleTrailingWhitespace,
+    ),
+    ("Toggle Ignored Files", Action::ToggleIgnoredFiles),
+    (
+        "Toggle Comment Continuation",
+        Action::ToggleCommentContinuation,
+    ),
+    ("Toggle Ruler", Action::ToggleRuler),
+    ("Toggle Word Count", Action::ToggleWordCount),
+    ("Reflow Paragraph", Action::ReflowParagraph),
+    ("Toggle Soft Wrap", Action::ToggleSoftWrap),
+    ("Toggle Overwrite", Action::ToggleOverwrite),
+    ("Toggle Auto-save", Action::ToggleAutoSave),
+    ("Toggle Read-only", Action::ToggleReadOnly),
+    ("Repeat Last Edit", Action::RepeatLastEdit),
+    ("Record Macro", Action::RecordMacro),
+    ("Play Macro", Action::PlayMacro),
+    ("Insert Timestamp", Action::InsertTimestamp),
+    ("Increment Number", Action::IncrementNumber),
+    ("Decrement Number", Action::DecrementNumber),
+    ("Block Start", Action::BlockStart),
+    ("Block End", Action::BlockEnd),
+    ("Toggle Clock", Action::ToggleClock),
+    ("Help", Action::Help),
+    ("Command Palette", Action::CommandPalette),
+    ("Quit", Action::Quit),
+    ("Force Quit", Action::ForceQuit),
+];
 
 pub struct Editor {
     reader: Reader,
     output: Output,
+    config: Config,
     quit_times: u8,
+    /// Keys captured so far while a macro is being recorded. Only keys read by the main loop
+    /// are captured: answers typed into prompts aren't, so a replayed key that opens a prompt
+    /// waits for the answer to be typed again.
+    recording: Option<Vec<KeyEvent>>,
+    last_macro: Vec<KeyEvent>,
+    last_input: Instant,
 }
 
 impl Editor {
-    pub fn new() -> Self {
-        Self {
+    pub fn new() -> crossterm::Result<Self> {
+        let mut output = Output::new()?;
+        let config = match Config::load() {
+            Ok(config) => {
+                if let Some(keys) = config.keymap.describe(Action::Help) {
+                    output.status_message.set_message(format!("HELP: {}", keys));
+                }
+                config
+            }
+            Err(err) => {
+                output
+                    .status_message
+                    .set_message(format!("Config error: {}", err));
+                Config::new()
+            }
+        };
+        output.bell = config.bell;
+        output.large_file_bytes = config.large_file_bytes;
+        output.cursor_shapes = config.cursor_shapes;
+        output.copy_command = config.copy_command.clone();
+        output.status_message.timeout = config.status_message_timeout;
+        output.cursor_controller.scrolloff = config.scrolloff;
+        output.ruler_column = config.ruler_column;
+        output.long_line_length = config.max_line_length;
+        output.wrap_column = config.wrap_column;
+        output.timestamp_format = config.timestamp_format.clone();
+        output.open_args()?;
+        Ok(Self {
             reader: Reader,
-            output: Output::new(),
-            quit_times: QUIT_TIMES,
+            output,
+            quit_times: config.quit_times,
+            config,
+            recording: None,
+            last_macro: Vec::new(),
+            last_input: Instant::now(),
+        })
+    }
+
+    /// Runs time-based work when no input arrived within a poll interval.
+    fn tick(&mut self) {
+        if let Some(delay) = self.output.auto_save_after {
+            if self.output.dirty > 0 && self.last_input.elapsed() >= delay {
+                self.output.auto_save();
+            }
         }
     }
 
     pub fn process_keypress(&mut self) -> crossterm::Result<bool> {
-        match self.reader.read_key()? {
+        let event = match self.reader.read_event()? {
+            Some(event) => event,
+            None => {
+                self.tick();
+                return Ok(true);
+            }
+        };
+        self.last_input = Instant::now();
+        match event {
+            Event::Key(key_event) => {
+                if let Some(recording) = self.recording.as_mut() {
+                    let is_macro_key = matches!(
+                        self.config.keymap.action(key_event),
+                        Some(Action::RecordMacro | Action::PlayMacro)
+                    );
+                    if !is_macro_key {
+                        recording.push(key_event);
+                    }
+                }
+                self.process_key(key_event)
+            }
+            Event::Mouse(mouse_event) => {
+                self.process_mouse(mouse_event);
+                Ok(true)
+            }
+            Event::Resize(columns, rows) => {
+                self.output.resize(columns, rows);
+                Ok(true)
+            }
+        }
+    }
+
+    fn toggle_macro_recording(&mut self) {
+        match self.recording.take() {
+            Some(recording) => {
+                self.output
+                    .status_message
+                    .set_message(format!("Recorded macro of {} keys", recording.len()));
+                self.last_macro = recording;
+            }
+            None => {
+                let keys = self.keys_for(Action::RecordMacro);
+                self.output.status_message.set_message(format!(
+                    "Recording macro... ({} to stop, prompt answers aren't recorded)",
+                    keys
+                ));
+                self.recording = Some(Vec::new());
+            }
+        }
+    }
+
+    fn replay_macro(&mut self) -> crossterm::Result<bool> {
+        if self.recording.is_some() {
+            self.output
+                .status_message
+                .set_message("Cannot replay a macro while recording".into());
+            return Ok(true);
+        }
+        if self.last_macro.is_empty() {
+            self.output
+                .status_message
+                .set_message("No macro recorded".into());
+            return Ok(true);
+        }
+        let times = match self.output.prompt("Replay macro how many times: {}") {
+            Some(times) => match times.trim().parse::<usize>() {
+                Ok(times) => times,
+                Err(_) => {
+                    self.output
+                        .status_message
+                        .set_message(format!("Invalid count: {}", times));
+                    return Ok(true);
+                }
+            },
+            None => return Ok(true),
+        };
+        let keys = self.last_macro.clone();
+        for _ in 0..times {
+            for key_event in &keys {
+                if !self.process_key(*key_event)? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// The keys bound to `action`, or its command palette name if it has none.
+    fn keys_for(&self, action: Action) -> String {
+        self.config.keymap.describe(action).unwrap_or_else(|| {
+            COMMANDS
+                .iter()
+                .find(|(_, command)| *command == action)
+                .map_or("its command", |(name, _)| name)
+                .to_string()
+        })
+    }
+
+    /// Lists the bound keys of every command, as currently configured.
+    fn help(&self) -> String {
+        let bound = COMMANDS
+            .iter()
+            .filter_map(|(name, action)| {
+                self.config
+                    .keymap
+                    .describe(*action)
+                    .map(|keys| format!("{} = {}", keys, name))
+            })
+            .collect::<Vec<_>>();
+        format!("HELP: {}", bound.join(" | "))
+    }
+
+    /// Lets the user pick a named action and runs it.
+    fn command_palette(&mut self) -> crossterm::Result<bool> {
+        let names = COMMANDS
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        let picked = self
+            .output
+            .pick("Command: {} (Use ESC / Arrows / Enter)", &names);
+        match COMMANDS
+            .iter()
+            .find(|(name, _)| Some(*name) == picked.as_deref())
+        {
+            Some((_, action)) => self.run_action(*action),
+            None => Ok(true),
+        }
+    }
+
+    fn process_key(&mut self, key_event: KeyEvent) -> crossterm::Result<bool> {
+        if let Some(action) = self.config.keymap.action(key_event) {
+            return self.run_action(action);
+        }
+        match key_event {
+            KeyEvent {
+                code: KeyCode::Tab,
+                modifiers: KeyModifiers::NONE,
+            } if self.output.cursor_controller.selection().is_some() => {
+                self.output.apply_edit(EditCommand::Indent)
+            }
             KeyEvent {
-                code: KeyCode::Char('q'),
-                modifiers: KeyModifiers::CONTROL,
-            } => {
-                if self.output.dirty > 0 && self.quit_times > 0 {
+                code: code @ (KeyCode::Char(..) | KeyCode::Tab),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+            } => self.output.apply_edit(EditCommand::InsertChar(match code {
+                KeyCode::Tab => '\t',
+                KeyCode::Char(ch) => ch,
+                _ => unreachable!(),
+            })),
+            _ => {}
+        }
+        self.quit_times = self.config.quit_times;
+        Ok(true)
+    }
+
+    fn run_action(&mut self, action: Action) -> crossterm::Result<bool> {
+        match action {
+            Action::RecordMacro => self.toggle_macro_recording(),
+            Action::PlayMacro => return self.replay_macro(),
+            Action::Quit => {
+                if self.output.any_dirty() && self.quit_times > 0 {
                     self.output.status_message.set_message(format!(
-                        "WARNING!!! File has unsaved changes. Press Ctrl-Q {} more times to quit.",
+                        "WARNING!!! File has unsaved changes. Press {} {} more times to quit.",
+                        self.keys_for(Action::Quit),
                         self.quit_times
                     ));
                     self.quit_times -= 1;
@@ -35,21 +313,23 @@ impl Editor {
                 }
                 return Ok(false);
             }
-            KeyEvent {
-                code:
-                    direction @ (KeyCode::Up
-                    | KeyCode::Down
-                    | KeyCode::Left
-                    | KeyCode::Right
-                    | KeyCode::Home
-                    | KeyCode::End),
-                modifiers: KeyModifiers::NONE,
-            } => self.output.move_cursor(direction),
-            KeyEvent {
-                code: val @ (KeyCode::PageUp | KeyCode::PageDown),
-                modifiers: KeyModifiers::NONE,
-            } => {
-                if matches!(val, KeyCode::PageUp) {
+            Action::ForceQuit => return Ok(false),
+            Action::MoveUp => self.output.move_cursor(KeyCode::Up),
+            Action::MoveDown => self.output.move_cursor(KeyCode::Down),
+            Action::MoveLeft => self.output.move_cursor(KeyCode::Left),
+            Action::MoveRight => self.output.move_cursor(KeyCode::Right),
+            Action::MoveHome => self.output.move_cursor(KeyCode::Home),
+            Action::MoveEnd => self.output.move_cursor(KeyCode::End),
+            Action::SelectUp => self.output.select(KeyCode::Up),
+            Action::SelectDown => self.output.select(KeyCode::Down),
+            Action::SelectLeft => self.output.select(KeyCode::Left),
+            Action::SelectRight => self.output.select(KeyCode::Right),
+            Action::SelectHome => self.output.select(KeyCode::Home),
+            Action::SelectEnd => self.output.select(KeyCode::End),
+            Action::WordLeft => self.output.move_word(SearchDirection::Backward),
+            Action::WordRight => self.output.move_word(SearchDirection::Forward),
+            Action::PageUp | Action::PageDown => {
+                if action == Action::PageUp {
                     self.output.cursor_controller.cursor_y =
                         self.output.cursor_controller.row_offset
                 } else {
@@ -59,86 +339,141 @@ impl Editor {
                     );
                 }
                 (0..self.output.win_size.1).for_each(|_| {
-                    self.output.move_cursor(if matches!(val, KeyCode::PageUp) {
+                    self.output.move_cursor(if action == Action::PageUp {
                         KeyCode::Up
                     } else {
                         KeyCode::Down
                     });
                 })
             }
-            KeyEvent {
-                code: KeyCode::Char('s'),
-                modifiers: KeyModifiers::CONTROL,
-            } => {
+            Action::HalfPageUp => self.output.scroll_half_page(SearchDirection::Backward),
+            Action::HalfPageDown => self.output.scroll_half_page(SearchDirection::Forward),
+            Action::Save => {
                 self.output.save_file()?;
             }
-            KeyEvent {
-                code: KeyCode::Char('f'),
-                modifiers: KeyModifiers::CONTROL,
-            } => {
+            Action::Find => {
                 self.output.find()?;
             }
-            KeyEvent {
-                code: key @ (KeyCode::Backspace | KeyCode::Delete),
-                modifiers: KeyModifiers::NONE,
-            } => {
-                if matches!(key, KeyCode::Delete) {
-                    self.output.move_cursor(KeyCode::Right)
-                }
-                self.output.delete_char()
-            }
-            KeyEvent {
-                code: KeyCode::Enter,
-                modifiers: KeyModifiers::NONE,
-            } => {
+            Action::Replace => self.output.replace(),
+            Action::DeleteBackward => self.output.apply_edit(EditCommand::DeleteBackward),
+            Action::DeleteForward => self.output.apply_edit(EditCommand::DeleteForward),
+            Action::DeletePreviousWord => self
+                .output
+                .apply_edit(EditCommand::DeleteWord(SearchDirection::Backward)),
+            Action::DeleteNextWord => self
+                .output
+                .apply_edit(EditCommand::DeleteWord(SearchDirection::Forward)),
+            Action::Copy => self.output.copy_selection(),
+            Action::CopyToCommand => self.output.copy_to_command(),
+            Action::FilterThroughCommand => self.output.filter_through_command(),
+            Action::Paste => self.output.apply_edit(EditCommand::Paste),
+            Action::Newline => {
                 if self.output.editor_rows.file_type == FileType::DIR {
-                    self.output.open_file(
-                        self.output
-                            .editor_rows
-                            .get_editor_row(self.output.cursor_controller.cursor_y)
-                            .row_content
-                            .clone()
-                            .into(),
-                    )?;
+                    if let Some(entry) = self
+                        .output
+                        .editor_rows
+                        .get_entry(self.output.cursor_controller.cursor_y)
+                    {
+                        self.output.open_file(entry.clone())?;
+                    }
                 } else {
-                    self.output.insert_newline()
+                    self.output.apply_edit(EditCommand::InsertNewline)
                 }
             }
-            KeyEvent {
-                code: code @ (KeyCode::Char(..) | KeyCode::Tab),
-                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
-            } => self.output.insert_char(match code {
-                KeyCode::Tab => '\t',
-                KeyCode::Char(ch) => ch,
-                _ => unreachable!(),
-            }),
-            KeyEvent {
-                code: KeyCode::Char('o'),
-                modifiers: KeyModifiers::CONTROL,
-            } => {
+            Action::Unindent => self.output.apply_edit(EditCommand::Unindent),
+            Action::AddCursorAtNextMatch => self.output.add_caret_at_next_match(),
+            Action::AddCursorBelow => self.output.add_caret_below(),
+            Action::ClearCursors => self.output.clear_carets(),
+            Action::JoinLines => self.output.apply_edit(EditCommand::JoinLines),
+            Action::DeleteToLineEnd => self
+                .output
+                .apply_edit(EditCommand::DeleteToLineEdge(SearchDirection::Forward)),
+            Action::DeleteToLineStart => self
+                .output
+                .apply_edit(EditCommand::DeleteToLineEdge(SearchDirection::Backward)),
+            Action::RepeatLastEdit => self.output.repeat_last_edit(),
+            Action::NewBuffer => self.output.new_buffer()?,
+            Action::QuickOpen => self.output.quick_open()?,
+            Action::CommandPalette => return self.command_palette(),
+            Action::GoToLine => self.output.goto_line(),
+            Action::GoToPercent => self.output.goto_percent(),
+            Action::ToggleReadOnly => self.output.toggle_read_only(),
+            Action::ToggleAutoSave => self.output.toggle_auto_save(),
+            Action::IncrementNumber => self.output.apply_edit(EditCommand::Increment(1)),
+            Action::DecrementNumber => self.output.apply_edit(EditCommand::Increment(-1)),
+            Action::InsertTimestamp => self.output.insert_timestamp(),
+            Action::ReflowParagraph => self.output.reflow_paragraph(),
+            Action::ToggleSoftWrap => self.output.toggle_soft_wrap(),
+            Action::ToggleOverwrite => self.output.toggle_overwrite(),
+            Action::CloseBuffer => self.output.close_buffer()?,
+            Action::NextBuffer => self.output.cycle_buffer(SearchDirection::Forward),
+            Action::PreviousBuffer => self.output.cycle_buffer(SearchDirection::Backward),
+            Action::BlockStart => self.output.jump_to_block_edge(SearchDirection::Backward),
+            Action::BlockEnd => self.output.jump_to_block_edge(SearchDirection::Forward),
+            Action::SaveAs => self.output.save_as()?,
+            Action::SaveAll => self.output.save_all()?,
+            Action::OpenFile => {
                 let open_prompt: Option<PathBuf> = self
                     .output
-                    .prompt("Open file: {} (ESC to cancel)")
+                    .prompt_path("Open file: {} (ESC to cancel)")
                     .map(|v| v.into());
-                match open_prompt {
-                    Some(open_file) => {
-                        self.output.open_file(open_file)?;
-                    }
-                    None => {}
+                if let Some(open_file) = open_prompt {
+                    self.output.open_file(open_file)?;
                 }
             }
-            KeyEvent {
-                code: KeyCode::Char('h'),
-                modifiers: KeyModifiers::CONTROL,
-            } => self.output.status_message.set_message(
-                "HELP: Ctrl-S = Save | Ctrl-Q = Quit | Ctrl-F = Find | Ctrl-O = Open".into(),
-            ),
-            _ => {}
+            Action::OpenFileUnderCursor => self.output.open_file_under_cursor()?,
+            Action::ToggleBookmark => self.output.toggle_bookmark(),
+            Action::ToggleFold => self.output.toggle_fold(),
+            Action::NextBookmark => self.output.jump_to_bookmark(SearchDirection::Forward),
+            Action::PreviousBookmark => self.output.jump_to_bookmark(SearchDirection::Backward),
+            Action::CycleCase => self.output.cycle_case(),
+            Action::ToggleLongLines => self.output.toggle_long_lines(),
+            Action::ToggleTrailingWhitespace => self.output.toggle_trailing_whitespace(),
+            Action::ToggleIgnoredFiles => self.output.toggle_ignored_files()?,
+            Action::ToggleCommentContinuation => self.output.toggle_comment_continuation(),
+            Action::ToggleRuler => self.output.toggle_ruler(),
+            Action::SortLines => {
+                if let Some(order) = self
+                    .output
+                    .prompt("Sort lines: {} (a = ascending, d = descending, add i to ignore case)")
+                {
+                    let order = order.to_lowercase();
+                    self.output
+                        .sort_lines(order.contains('d'), order.contains('i'));
+                }
+            }
+            Action::RemoveDuplicateLines => self.output.remove_duplicate_lines(),
+            Action::ToggleWordCount => self.output.show_word_count = !self.output.show_word_count,
+            Action::ToggleClock => self.output.show_clock = !self.output.show_clock,
+            Action::Help => {
+                let help = self.help();
+                self.output.status_message.set_message(help)
+            }
         }
-        self.quit_times = QUIT_TIMES;
+        self.quit_times = self.config.quit_times;
         Ok(true)
     }
 
+    fn process_mouse(&mut self, mouse_event: MouseEvent) {
+        match mouse_event {
+            MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            } => self.output.click(column as usize, row as usize),
+            MouseEvent {
+                kind: MouseEventKind::ScrollUp,
+                ..
+            } => self.output.scroll_view(SearchDirection::Backward),
+            MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                ..
+            } => self.output.scroll_view(SearchDirection::Forward),
+            _ => {}
+        }
+    }
+
     pub fn run(&mut self) -> crossterm::Result<bool> {
         self.output.refresh_screen()?;
         self.process_keypress()
@@ -1,4 +1,7 @@
-use std::io::{self, stdout};
+use std::{
+    io::{self, stdout},
+    mem,
+};
 
 pub struct EditorContents {
     content: String,
@@ -18,6 +21,17 @@ impl EditorContents {
     pub fn push_str(&mut self, string: &str) {
         self.content.push_str(string)
     }
+
+    /// Removes and returns everything queued so far without writing it out.
+    pub fn take(&mut self) -> String {
+        mem::take(&mut self.content)
+    }
+}
+
+impl Default for EditorContents {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl io::Write for EditorContents {
@@ -1,10 +1,19 @@
 use std::{
-    fs,
-    io::{self, ErrorKind, Write},
+    collections::BTreeMap,
+    fmt, fs,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    iter,
+    ops::Range,
     path::PathBuf,
 };
 
-use crate::{output::Output, row::Row, syntax_highlighting::SyntaxHighlight, TAB_STOP};
+use crate::{
+    gitignore::IgnoreRules,
+    output::Output,
+    row::Row,
+    syntax_highlighting::{HighlightType, SyntaxHighlight},
+    BRACKET_SCAN_LIMIT, LAZY_LOAD_BYTES, TAB_STOP,
+};
 
 #[derive(PartialEq, Eq)]
 
@@ -19,11 +28,102 @@ pub enum EditMode {
     READONLY,
 }
 
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum Encoding {
+    UTF8,
+    UTF8BOM,
+}
+
+impl Encoding {
+    const BOM: &'static str = "\u{feff}";
+
+    fn detect(contents: &[u8]) -> Self {
+        if contents.starts_with(Self::BOM.as_bytes()) {
+            Encoding::UTF8BOM
+        } else {
+            Encoding::UTF8
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Encoding::UTF8 => "utf-8",
+            Encoding::UTF8BOM => "utf-8 bom",
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum LineEnding {
+    LF,
+    CRLF,
+}
+
+impl LineEnding {
+    /// Uses the ending of the first line in `contents`, defaulting to `LF`.
+    fn detect(contents: &[u8]) -> Self {
+        match contents.iter().position(|b| *b == b'\n') {
+            Some(i) if i > 0 && contents[i - 1] == b'\r' => LineEnding::CRLF,
+            _ => LineEnding::LF,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            LineEnding::LF => "\n",
+            LineEnding::CRLF => "\r\n",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            LineEnding::LF => "LF",
+            LineEnding::CRLF => "CRLF",
+        }
+    }
+}
+
+/// What `EditorRows::save` wrote.
+pub struct SaveReport {
+    pub name: String,
+    pub lines: usize,
+    pub bytes: usize,
+    /// Whether the file didn't exist before the save.
+    pub created: bool,
+}
+
+impl fmt::Display for SaveReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} lines, {} bytes)",
+            self.name, self.lines, self.bytes
+        )
+    }
+}
+
 pub struct EditorRows {
     pub row_contents: Vec<Row>,
     pub filename: Option<PathBuf>,
     pub file_type: FileType,
     pub edit_mode: EditMode,
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    /// Whether saving ends the last row with a line ending, as the loaded file did.
+    pub final_newline: bool,
+    /// Folded regions, mapping the row a fold hangs off to the last row it hides.
+    pub folds: BTreeMap<usize, usize>,
+    /// The directory a directory buffer lists.
+    pub listed_dir: Option<PathBuf>,
+    /// Paths of the entries listed in a directory buffer, one per row.
+    pub entries: Vec<PathBuf>,
+    /// The rows as last loaded or saved, which changes are marked against.
+    pub baseline: Vec<String>,
+    /// Remaining lines of a large file that haven't been read into `row_contents` yet.
+    pub pending_lines: Option<io::Lines<BufReader<fs::File>>>,
+    /// Why reading `pending_lines` stopped early. The rest of the file is never loaded, so the
+    /// buffer stays unsaveable rather than overwriting the file with only part of it.
+    pub read_error: Option<String>,
 }
 
 impl EditorRows {
@@ -33,43 +133,184 @@ impl EditorRows {
             filename: None,
             file_type: FileType::FILE,
             edit_mode: EditMode::NORMAL,
+            encoding: Encoding::UTF8,
+            line_ending: LineEnding::LF,
+            final_newline: true,
+            folds: BTreeMap::new(),
+            listed_dir: None,
+            entries: Vec::new(),
+            baseline: Vec::new(),
+            pending_lines: None,
+            read_error: None,
         }
     }
 
     pub fn from_file(
         file: PathBuf,
         syntax_highlight: &mut Option<Box<dyn SyntaxHighlight>>,
-    ) -> Self {
+    ) -> io::Result<Self> {
         if !file.exists() {
-            return Self {
-                row_contents: Vec::new(),
+            return Ok(Self {
                 filename: Some(file),
-                file_type: FileType::FILE,
-                edit_mode: EditMode::NORMAL,
-            };
+                ..Self::new()
+            });
         }
 
-        let file_contents = fs::read_to_string(&file).expect("Unable to read file");
-        let mut row_contents = Vec::new();
         file.extension()
             .and_then(|ext| ext.to_str())
             .map(|ext| Output::select_syntax(ext).map(|syntax| syntax_highlight.insert(syntax)));
-        file_contents.lines().enumerate().for_each(|(i, line)| {
-            let mut row = Row::new(line.into(), String::new());
-            Self::render_row(&mut row);
-            row_contents.push(row);
-            if let Some(it) = syntax_highlight {
-                it.update_syntax(i, &mut row_contents)
+        let file_size = fs::metadata(&file).map(|it| it.len()).unwrap_or(0);
+        if file_size > LAZY_LOAD_BYTES {
+            let mut reader = BufReader::new(fs::File::open(&file)?);
+            let head = reader.fill_buf()?;
+            let (encoding, line_ending) = (Encoding::detect(head), LineEnding::detect(head));
+            if encoding == Encoding::UTF8BOM {
+                reader.consume(Encoding::BOM.len());
             }
-        });
-        Self {
+            return Ok(Self {
+                final_newline: Self::ends_with_newline(&file)?,
+                filename: Some(file),
+                encoding,
+                line_ending,
+                pending_lines: Some(reader.lines()),
+                ..Self::new()
+            });
+        }
+
+        let file_contents = fs::read_to_string(&file)?;
+        Ok(Self {
             filename: Some(file),
+            ..Self::from_str(&file_contents, syntax_highlight)
+        })
+    }
+
+    /// Checks the last byte of `file`, treating an empty file as ending in a newline.
+    fn ends_with_newline(file: &PathBuf) -> io::Result<bool> {
+        let mut file = fs::File::open(file)?;
+        if file.seek(SeekFrom::End(0))? == 0 {
+            return Ok(true);
+        }
+        file.seek(SeekFrom::End(-1))?;
+        let mut last = [0];
+        file.read_exact(&mut last)?;
+        Ok(last[0] == b'\n')
+    }
+
+    /// Builds unnamed rows from in-memory `contents`, highlighting them with `syntax_highlight`.
+    pub fn from_str(
+        contents: &str,
+        syntax_highlight: &mut Option<Box<dyn SyntaxHighlight>>,
+    ) -> Self {
+        let encoding = Encoding::detect(contents.as_bytes());
+        let line_ending = LineEnding::detect(contents.as_bytes());
+        let mut row_contents = Vec::new();
+        contents
+            .strip_prefix(Encoding::BOM)
+            .unwrap_or(contents)
+            .lines()
+            .enumerate()
+            .for_each(|(i, line)| {
+                let mut row = Row::new(line.into(), String::new());
+                Self::render_row(&mut row);
+                row_contents.push(row);
+                if let Some(it) = syntax_highlight {
+                    it.update_syntax(i, &mut row_contents)
+                }
+            });
+        Self {
+            baseline: row_contents
+                .iter()
+                .map(|row| row.row_content.clone())
+                .collect(),
             row_contents,
-            file_type: FileType::FILE,
-            edit_mode: EditMode::NORMAL,
+            encoding,
+            line_ending,
+            final_newline: contents.is_empty() || contents.ends_with('\n'),
+            ..Self::new()
         }
     }
 
+    /// Lists `dir` as a read-only buffer, with a `..` entry for the parent followed
+    /// by subdirectories and then files, each sorted by name. Rows show base names,
+    /// with a trailing `/` for subdirectories. Entries `.gitignore` excludes are left
+    /// out unless `show_ignored` is set.
+    pub fn from_dir(dir: PathBuf, show_ignored: bool) -> io::Result<Self> {
+        let dir = fs::canonicalize(&dir).unwrap_or(dir);
+        let rules = if show_ignored {
+            IgnoreRules::default()
+        } else {
+            IgnoreRules::for_dir(&dir)
+        };
+        let mut entries = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| show_ignored || !rules.is_ignored(path, path.is_dir()))
+            .collect::<Vec<PathBuf>>();
+        entries.sort_by(|a, b| b.is_dir().cmp(&a.is_dir()).then_with(|| a.cmp(b)));
+        let mut row_contents = Vec::with_capacity(entries.len() + 1);
+        if let Some(parent) = dir.parent() {
+            entries.insert(0, parent.into());
+            row_contents.push(Row::new("..".into(), String::new()));
+        }
+        for entry in &entries[row_contents.len()..] {
+            let mut name: String = entry
+                .file_name()
+                .map(|name| name.to_string_lossy().into())
+                .unwrap_or_default();
+            if entry.is_dir() {
+                name.push('/');
+            }
+            row_contents.push(Row::new(name, String::new()));
+        }
+        row_contents.iter_mut().for_each(Self::render_row);
+        Ok(Self {
+            row_contents,
+            listed_dir: Some(dir),
+            file_type: FileType::DIR,
+            edit_mode: EditMode::READONLY,
+            entries,
+            ..Self::new()
+        })
+    }
+
+    pub fn get_entry(&self, at: usize) -> Option<&PathBuf> {
+        self.entries.get(at)
+    }
+
+    pub fn is_fully_loaded(&self) -> bool {
+        self.pending_lines.is_none()
+    }
+
+    /// Reads pending lines of a lazily opened file until at least `rows` rows are
+    /// available, the file is exhausted or a line can't be read.
+    pub fn load_rows(&mut self, rows: usize, syntax_highlight: Option<&dyn SyntaxHighlight>) {
+        if self.read_error.is_some() {
+            return;
+        }
+        while self.row_contents.len() < rows {
+            let line = match self.pending_lines.as_mut().and_then(|lines| lines.next()) {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => {
+                    self.read_error = Some(err.to_string());
+                    return;
+                }
+                None => {
+                    self.pending_lines = None;
+                    return;
+                }
+            };
+            let at = self.row_contents.len();
+            self.baseline.push(line.clone());
+            self.insert_row(at, line);
+            if let Some(it) = syntax_highlight {
+                it.update_syntax(at, &mut self.row_contents)
+            }
+        }
+    }
+
+    pub fn load_all(&mut self, syntax_highlight: Option<&dyn SyntaxHighlight>) {
+        self.load_rows(usize::MAX, syntax_highlight)
+    }
+
     pub fn number_of_rows(&self) -> usize {
         self.row_contents.len()
     }
@@ -86,10 +327,106 @@ impl EditorRows {
         &self.row_contents[at]
     }
 
+    /// Iterates over the loaded rows as `(index, row_content, render)`.
+    pub fn rows(&self) -> impl Iterator<Item = (usize, &str, &str)> {
+        self.row_contents
+            .iter()
+            .enumerate()
+            .map(|(at, row)| (at, row.row_content.as_str(), row.render.as_str()))
+    }
+
+    /// Iterates over the runs of equally highlighted render positions in the row at `at`.
+    pub fn highlight_spans(
+        &self,
+        at: usize,
+    ) -> impl Iterator<Item = (Range<usize>, HighlightType)> + '_ {
+        let highlight = &self.row_contents[at].highlight;
+        let mut start = 0;
+        iter::from_fn(move || {
+            let first = *highlight.get(start)?;
+            let len = highlight[start..]
+                .iter()
+                .take_while(|highlight_type| **highlight_type == first)
+                .count();
+            start += len;
+            Some((start - len..start, first))
+        })
+    }
+
     pub fn get_editor_row_mut(&mut self, at: usize) -> &mut Row {
         &mut self.row_contents[at]
     }
 
+    /// Splices `text`, which may span several lines, into the row at `at` before the
+    /// character index `x`, rendering and highlighting each touched row once. Returns
+    /// the position just after the inserted text.
+    pub fn insert_str(
+        &mut self,
+        at: usize,
+        x: usize,
+        text: &str,
+        syntax_highlight: Option<&dyn SyntaxHighlight>,
+    ) -> (usize, usize) {
+        if at == self.number_of_rows() {
+            self.insert_row(at, String::new());
+        }
+        let row = &mut self.row_contents[at];
+        let tail = row.row_content.split_off(row.byte_index(x));
+        let mut lines = text.split('\n');
+        row.row_content.push_str(lines.next().unwrap_or_default());
+        let new_rows = lines
+            .map(|line| Row::new(line.into(), String::new()))
+            .collect::<Vec<Row>>();
+        let last = at + new_rows.len();
+        self.row_contents.splice(at + 1..at + 1, new_rows);
+        let end_x = self.row_contents[last].char_count();
+        self.row_contents[last].row_content.push_str(&tail);
+        for row in &mut self.row_contents[at..=last] {
+            Self::render_row(row);
+        }
+        if let Some(syntax_highlight) = syntax_highlight {
+            for y in at..=last {
+                syntax_highlight.update_row_syntax(y, &mut self.row_contents);
+            }
+            syntax_highlight.update_syntax(last, &mut self.row_contents);
+        }
+        (end_x, last)
+    }
+
+    /// Removes the text between the `(x, y)` positions `start` and `end`, joining the
+    /// boundary rows and re-rendering and highlighting the result once. Columns are
+    /// character indices. Returns the removed text.
+    pub fn delete_range(
+        &mut self,
+        start: (usize, usize),
+        end: (usize, usize),
+        syntax_highlight: Option<&dyn SyntaxHighlight>,
+    ) -> String {
+        let ((start_x, start_y), (end_x, end_y)) = (start, end);
+        let end_row = &self.row_contents[end_y];
+        let tail = end_row.row_content[end_row.byte_index(end_x)..].to_string();
+        let mut removed = self
+            .row_contents
+            .drain(start_y + 1..=end_y)
+            .map(|row| row.row_content)
+            .collect::<Vec<String>>();
+        let row = &mut self.row_contents[start_y];
+        let head = row.row_content.split_off(row.byte_index(start_x));
+        match removed.last_mut() {
+            Some(last) => {
+                last.truncate(last.len() - tail.len());
+                removed.insert(0, head);
+            }
+            None => removed.push(head[..head.len() - tail.len()].to_string()),
+        }
+        row.row_content.push_str(&tail);
+        Self::render_row(row);
+        if let Some(syntax_highlight) = syntax_highlight {
+            syntax_highlight.update_syntax(start_y, &mut self.row_contents);
+        }
+        removed.join("\n")
+    }
+
     pub fn render_row(row: &mut Row) {
         let mut index = 0;
         let capacity = row
@@ -117,24 +454,218 @@ impl EditorRows {
         self.row_contents.insert(at, new_row);
     }
 
-    pub fn save(&mut self) -> io::Result<usize> {
+    pub fn save(&mut self) -> io::Result<SaveReport> {
+        if !self.is_fully_loaded() {
+            return Err(io::Error::other(match &self.read_error {
+                Some(err) => format!("file could not be read: {}", err),
+                None => "file is not fully loaded".into(),
+            }));
+        }
         match &self.filename {
-            None => Err(io::Error::new(ErrorKind::Other, "no file name specified")),
+            None => Err(io::Error::other("no file name specified")),
+            Some(name) if name.is_dir() => Err(io::Error::other("path is a directory")),
             Some(name) => {
-                let mut file = fs::OpenOptions::new().write(true).create(true).open(name)?;
-                let contents: String = self
+                let created = !name.exists();
+                let mut file = fs::OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(name)?;
+                let mut contents: String = self
                     .row_contents
                     .iter()
                     .map(|it| it.row_content.as_str())
                     .collect::<Vec<&str>>()
-                    .join("\n");
+                    .join(self.line_ending.as_str());
+                if self.final_newline && !self.row_contents.is_empty() {
+                    contents.push_str(self.line_ending.as_str());
+                }
+                if self.encoding == Encoding::UTF8BOM {
+                    contents.insert_str(0, Encoding::BOM);
+                }
                 file.set_len(contents.len() as u64)?;
                 file.write_all(contents.as_bytes())?;
-                Ok(contents.as_bytes().len())
+                self.baseline = self
+                    .row_contents
+                    .iter()
+                    .map(|row| row.row_content.clone())
+                    .collect();
+                Ok(SaveReport {
+                    name: name
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into())
+                        .unwrap_or_default(),
+                    lines: self.number_of_rows(),
+                    bytes: contents.len(),
+                    created,
+                })
             }
         }
     }
 
+    /// Whether saving would leave the file without a final newline: it was loaded
+    /// without one and the last row still has text after the last line break.
+    pub fn missing_final_newline(&self) -> bool {
+        !self.final_newline
+            && self.is_fully_loaded()
+            && self
+                .row_contents
+                .last()
+                .is_some_and(|row| !row.row_content.is_empty())
+    }
+
+    /// Finds the bracket matching the one at character `(x, y)`, scanning forwards or
+    /// backwards across rows while tracking nesting depth. Gives up after
+    /// `BRACKET_SCAN_LIMIT` characters, so huge files don't slow every redraw.
+    pub fn find_matching_bracket(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+        let bracket = self.row_contents.get(y)?.row_content.chars().nth(x)?;
+        let (nests, unnests, forward) = PAIRS.iter().find_map(|&(open, close)| {
+            if bracket == open {
+                Some((open, close, true))
+            } else if bracket == close {
+                Some((close, open, false))
+            } else {
+                None
+            }
+        })?;
+        let mut depth = 0usize;
+        let mut budget = BRACKET_SCAN_LIMIT;
+        let mut visit = |ch: char| -> Option<bool> {
+            budget = budget.checked_sub(1)?;
+            if ch == nests {
+                depth += 1;
+            } else if ch == unnests {
+                depth -= 1;
+            }
+            Some(depth == 0)
+        };
+        if forward {
+            for (row_y, row) in self.row_contents.iter().enumerate().skip(y) {
+                let skip = if row_y == y { x } else { 0 };
+                for (col, ch) in row.row_content.chars().enumerate().skip(skip) {
+                    if visit(ch)? {
+                        return Some((col, row_y));
+                    }
+                }
+            }
+        } else {
+            for row_y in (0..=y).rev() {
+                let row = &self.row_contents[row_y];
+                let count = row.char_count();
+                let skip = if row_y == y { count - 1 - x } else { 0 };
+                for (i, ch) in row.row_content.chars().rev().enumerate().skip(skip) {
+                    if visit(ch)? {
+                        return Some((count - 1 - i, row_y));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the row the fold hiding `at` hangs off, if `at` is hidden.
+    pub fn fold_containing(&self, at: usize) -> Option<usize> {
+        self.folds
+            .range(..at)
+            .next_back()
+            .filter(|(_, end)| at <= **end)
+            .map(|(start, _)| *start)
+    }
+
+    /// Returns the next row that isn't hidden by a fold, which may be one past the end.
+    pub fn next_visible_row(&self, at: usize) -> usize {
+        self.folds.get(&at).map_or(at + 1, |end| end + 1)
+    }
+
+    pub fn previous_visible_row(&self, at: usize) -> usize {
+        let previous = at.saturating_sub(1);
+        self.fold_containing(previous).unwrap_or(previous)
+    }
+
+    /// Steps `count` visible rows down from `at`, stopping at the last row.
+    pub fn visible_row_after(&self, at: usize, count: usize) -> usize {
+        let mut at = at;
+        for _ in 0..count {
+            let next = self.next_visible_row(at);
+            if next >= self.number_of_rows() {
+                break;
+            }
+            at = next;
+        }
+        at
+    }
+
+    /// Steps `count` visible rows up from `at`, stopping at the first row.
+    pub fn visible_row_before(&self, at: usize, count: usize) -> usize {
+        let mut at = at;
+        for _ in 0..count {
+            if at == 0 {
+                break;
+            }
+            at = self.previous_visible_row(at);
+        }
+        at
+    }
+
+    fn indentation(row: &Row) -> Option<usize> {
+        let trimmed = row.render.trim_start().len();
+        if trimmed == 0 {
+            None
+        } else {
+            Some(row.render.len() - trimmed)
+        }
+    }
+
+    /// Returns the first and last rows of the block around `at`: the rows indented at
+    /// least as deeply as it. Blank rows inside the block don't end it.
+    pub fn indent_block(&self, at: usize) -> Option<(usize, usize)> {
+        let base = self.row_contents.get(at).and_then(Self::indentation)?;
+        let in_block =
+            |row: &Row| Self::indentation(row).is_none_or(|indentation| indentation >= base);
+        let mut start = at;
+        for i in (0..at).rev() {
+            if !in_block(&self.row_contents[i]) {
+                break;
+            }
+            if Self::indentation(&self.row_contents[i]).is_some() {
+                start = i;
+            }
+        }
+        let mut end = at;
+        for i in at + 1..self.number_of_rows() {
+            if !in_block(&self.row_contents[i]) {
+                break;
+            }
+            if Self::indentation(&self.row_contents[i]).is_some() {
+                end = i;
+            }
+        }
+        Some((start, end))
+    }
+
+    /// Folds the rows after `at` that are indented deeper than it, returning how many
+    /// rows were hidden. Blank rows inside the block are folded with it.
+    pub fn fold(&mut self, at: usize) -> usize {
+        let base = match self.row_contents.get(at).and_then(Self::indentation) {
+            Some(base) => base,
+            None => return 0,
+        };
+        let mut end = at;
+        for i in at + 1..self.number_of_rows() {
+            match Self::indentation(&self.row_contents[i]) {
+                Some(indentation) if indentation > base => end = i,
+                Some(_) => break,
+                None => {}
+            }
+        }
+        if end > at {
+            self.folds.retain(|start, _| *start <= at || *start > end);
+            self.folds.insert(at, end);
+        }
+        end - at
+    }
+
     pub fn join_adjacent_rows(&mut self, at: usize) {
         let current_row = self.row_contents.remove(at);
         let previous_row = self.get_editor_row_mut(at - 1);
@@ -142,3 +673,73 @@ impl EditorRows {
         Self::render_row(previous_row);
     }
 }
+
+impl Default for EditorRows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_brackets_by_character_index() {
+        let rows = EditorRows::from_str("é(a)\n{ ö\n  [x] }\n", &mut None);
+        assert_eq!(rows.find_matching_bracket(1, 0), Some((3, 0)));
+        assert_eq!(rows.find_matching_bracket(3, 0), Some((1, 0)));
+        assert_eq!(rows.find_matching_bracket(0, 1), Some((6, 2)));
+        assert_eq!(rows.find_matching_bracket(6, 2), Some((0, 1)));
+        assert_eq!(rows.find_matching_bracket(0, 0), None);
+    }
+
+    #[test]
+    fn stops_scanning_for_a_bracket_after_the_limit() {
+        let contents = format!("({})\n", " ".repeat(BRACKET_SCAN_LIMIT));
+        let rows = EditorRows::from_str(&contents, &mut None);
+        assert_eq!(rows.find_matching_bracket(0, 0), None);
+    }
+
+    #[test]
+    fn builds_rows_from_a_string() {
+        let rows = EditorRows::from_str("fn main() {\r\n\tx\r\n}", &mut None);
+        assert_eq!(rows.number_of_rows(), 3);
+        assert_eq!(rows.get_row(1), "\tx");
+        assert_eq!(*rows.get_render(1), format!("{}x", " ".repeat(TAB_STOP)));
+        assert!(rows.line_ending == LineEnding::CRLF);
+        assert!(!rows.final_newline);
+        assert!(rows.filename.is_none());
+        assert_eq!(EditorRows::from_str("", &mut None).number_of_rows(), 0);
+    }
+
+    #[test]
+    fn highlights_rows_built_from_a_string() {
+        let mut syntax = Output::select_syntax("rs");
+        let rows = EditorRows::from_str("let x = 1;\n", &mut syntax);
+        assert!(syntax.is_some());
+        assert_eq!(rows.get_editor_row(0).highlight.len(), 10);
+        assert_eq!(
+            rows.get_editor_row(0).highlight[0],
+            HighlightType::Other(crossterm::style::Color::Yellow)
+        );
+    }
+
+    #[test]
+    fn stops_loading_at_an_unreadable_line() {
+        let path = std::env::temp_dir().join(format!("pound-unreadable-{}", std::process::id()));
+        fs::write(&path, b"first\n\xff\nlast\n").unwrap();
+        let mut rows = EditorRows {
+            filename: Some(path.clone()),
+            pending_lines: Some(BufReader::new(fs::File::open(&path).unwrap()).lines()),
+            ..EditorRows::new()
+        };
+        rows.load_all(None);
+        assert_eq!(rows.number_of_rows(), 1);
+        assert!(rows.read_error.is_some());
+        assert!(!rows.is_fully_loaded());
+        assert!(rows.save().is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"first\n\xff\nlast\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use std::{fs, path::Path};
+
+use crate::gitignore::IgnoreRules;
+
+/// Scores `candidate` against `query` as a case-insensitive subsequence match.
+/// Lower scores are better; `None` means the query doesn't match.
+pub fn score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate = candidate.to_lowercase();
+    let mut chars = candidate.char_indices();
+    let mut previous = None;
+    let mut score = 0;
+    for wanted in query.to_lowercase().chars() {
+        let (index, _) = chars.by_ref().find(|(_, ch)| *ch == wanted)?;
+        let at_boundary = index == 0 || candidate[..index].ends_with(['/', '_', '-', '.']);
+        match previous {
+            Some(previous) if index == previous + 1 => {}
+            _ if at_boundary => score += 1,
+            _ => score += 2 + index - previous.map_or(0, |previous| previous + 1),
+        }
+        previous = Some(index);
+    }
+    Some(score * 256 + candidate.len().min(255))
+}
+
+/// Recursively lists the files under `root` as paths relative to it, skipping those
+/// `.gitignore` excludes unless `show_ignored` is set.
+pub fn files_under(root: &Path, show_ignored: bool) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), IgnoreRules::for_dir(root))];
+    while let Some((dir, rules)) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+            if !show_ignored && rules.is_ignored(&path, file_type.is_dir()) {
+                continue;
+            }
+            if file_type.is_dir() {
+                let mut rules = rules.clone();
+                if !show_ignored {
+                    rules.add_dir(&path);
+                }
+                pending.push((path, rules));
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                files.push(relative.to_string_lossy().into_owned());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_consecutive_and_boundary_matches_above_scattered_ones() {
+        assert!(score("abc", "abcxyz") < score("abc", "axbxcx"));
+        assert!(score("fb", "foo_bar.rs") < score("fb", "fab.rs"));
+        assert!(score("main", "src/main.rs") < score("main", "domain.rs"));
+        assert!(score("MAIN", "src/main.rs").is_some());
+    }
+
+    #[test]
+    fn rejects_a_query_that_is_not_a_subsequence() {
+        assert_eq!(score("ba", "abc"), None);
+        assert_eq!(score("mainx", "src/main.rs"), None);
+        assert_eq!(score("", "anything"), Some("anything".len()));
+    }
+}
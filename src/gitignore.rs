@@ -0,0 +1,225 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Directory names hidden from listings even without a `.gitignore` rule.
+const ALWAYS_IGNORED: [&str; 3] = [".git", "target", "node_modules"];
+
+#[derive(Clone)]
+struct Rule {
+    /// Directory of the `.gitignore` the rule came from.
+    base: PathBuf,
+    glob: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+    /// Whether the glob matches the whole path below `base` rather than just the name.
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(base: &Path, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        Some(Self {
+            base: base.to_path_buf(),
+            glob: line.trim_start_matches('/').chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let relative = match path.strip_prefix(&self.base) {
+            Ok(relative) => relative,
+            Err(_) => return false,
+        };
+        let text = if self.anchored {
+            relative.to_string_lossy().replace('\\', "/")
+        } else {
+            match relative.file_name() {
+                Some(name) => name.to_string_lossy().into(),
+                None => return false,
+            }
+        };
+        glob_match(&self.glob, &text.chars().collect::<Vec<_>>())
+    }
+}
+
+/// Matches `text` against a gitignore glob, where `*` and `?` stay within one path
+/// component, `**` spans any number of them and `[...]` is a character class.
+fn glob_match(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            let (rest, at_component) = match rest.split_first() {
+                Some(('/', rest)) => (rest, true),
+                _ => (rest, false),
+            };
+            (0..=text.len())
+                .filter(|i| !at_component || *i == 0 || text[i - 1] == '/')
+                .any(|i| glob_match(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|i| *i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match(rest, &text[i..])),
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(ch) if *ch != '/') && glob_match(rest, &text[1..])
+        }
+        ['[', class @ ..] => match class.iter().skip(1).position(|ch| *ch == ']') {
+            Some(end) => {
+                let (class, rest) = (&class[..end + 1], &class[end + 2..]);
+                let (negated, class) = match class.split_first() {
+                    Some(('!' | '^', class)) => (true, class),
+                    _ => (false, class),
+                };
+                let ch = match text.first() {
+                    Some(ch) if *ch != '/' => *ch,
+                    _ => return false,
+                };
+                let mut in_class = false;
+                let mut i = 0;
+                while i < class.len() {
+                    if class.get(i + 1) == Some(&'-') && i + 2 < class.len() {
+                        in_class |= (class[i]..=class[i + 2]).contains(&ch);
+                        i += 3;
+                    } else {
+                        in_class |= class[i] == ch;
+                        i += 1;
+                    }
+                }
+                in_class != negated && glob_match(rest, &text[1..])
+            }
+            None => text.first() == Some(&'[') && glob_match(class, &text[1..]),
+        },
+        ['\\', escaped, rest @ ..] | [escaped, rest @ ..] => {
+            text.first() == Some(escaped) && glob_match(rest, &text[1..])
+        }
+    }
+}
+
+/// The `.gitignore` rules in effect for a directory, later rules taking precedence.
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    /// Collects the rules of `dir` and, inside a repository, of each directory up to
+    /// its root.
+    pub fn for_dir(dir: &Path) -> Self {
+        let in_repository = dir.ancestors().position(|dir| dir.join(".git").exists());
+        let mut rules = Self::default();
+        for dir in dir
+            .ancestors()
+            .take(in_repository.map_or(1, |root| root + 1))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+        {
+            rules.add_dir(dir);
+        }
+        rules
+    }
+
+    /// Adds the rules of `dir/.gitignore`, if there is one.
+    pub fn add_dir(&mut self, dir: &Path) {
+        if let Ok(contents) = fs::read_to_string(dir.join(".gitignore")) {
+            self.rules
+                .extend(contents.lines().filter_map(|line| Rule::parse(dir, line)));
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let name = path.file_name().map(|name| name.to_string_lossy());
+        if is_dir && name.is_some_and(|name| ALWAYS_IGNORED.contains(&name.as_ref())) {
+            return true;
+        }
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path, is_dir))
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(glob: &str, text: &str) -> bool {
+        glob_match(
+            &glob.chars().collect::<Vec<_>>(),
+            &text.chars().collect::<Vec<_>>(),
+        )
+    }
+
+    fn rules(gitignore: &str) -> IgnoreRules {
+        IgnoreRules {
+            rules: gitignore
+                .lines()
+                .filter_map(|line| Rule::parse(Path::new("/repo"), line))
+                .collect(),
+        }
+    }
+
+    fn ignored(rules: &IgnoreRules, path: &str, is_dir: bool) -> bool {
+        rules.is_ignored(&Path::new("/repo").join(path), is_dir)
+    }
+
+    #[test]
+    fn spans_components_only_with_a_double_star() {
+        assert!(glob("**/build", "build"));
+        assert!(glob("**/build", "a/b/build"));
+        assert!(!glob("**/build", "a/rebuild"));
+        assert!(glob("a/**/b", "a/b"));
+        assert!(glob("a/**/b", "a/x/y/b"));
+        assert!(!glob("a/**/b", "a/xb"));
+        assert!(glob("*.rs", "main.rs"));
+        assert!(!glob("*.rs", "src/main.rs"));
+        assert!(glob("[a-c]?.txt", "b1.txt"));
+        assert!(!glob("[!a-c]?.txt", "b1.txt"));
+    }
+
+    #[test]
+    fn lets_a_negated_rule_override_an_earlier_one() {
+        let rules = rules("# logs\n*.log\n!keep.log\n");
+        assert!(ignored(&rules, "debug.log", false));
+        assert!(!ignored(&rules, "keep.log", false));
+        assert!(!ignored(&rules, "notes.txt", false));
+    }
+
+    #[test]
+    fn matches_only_directories_with_a_trailing_slash() {
+        let rules = rules("build/\n");
+        assert!(ignored(&rules, "build", true));
+        assert!(ignored(&rules, "src/build", true));
+        assert!(!ignored(&rules, "build", false));
+    }
+
+    #[test]
+    fn anchors_patterns_containing_a_slash() {
+        let rules = rules("/out\ndoc/*.txt\ntmp\n");
+        assert!(ignored(&rules, "out", false));
+        assert!(!ignored(&rules, "src/out", false));
+        assert!(ignored(&rules, "doc/notes.txt", false));
+        assert!(!ignored(&rules, "src/doc/notes.txt", false));
+        assert!(ignored(&rules, "tmp", false));
+        assert!(ignored(&rules, "src/tmp", true));
+    }
+}
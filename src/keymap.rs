@@ -0,0 +1,653 @@
+use std::collections::HashMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+pub const fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+    KeyEvent { code, modifiers }
+}
+
+/// Everything a key can be bound to. Typing text is not an action: keys without a
+/// binding fall through to inserting their character.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    MoveHome,
+    MoveEnd,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    SelectHome,
+    SelectEnd,
+    WordLeft,
+    WordRight,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Newline,
+    DeleteBackward,
+    DeleteForward,
+    DeletePreviousWord,
+    DeleteNextWord,
+    DeleteToLineEnd,
+    DeleteToLineStart,
+    Unindent,
+    AddCursorAtNextMatch,
+    AddCursorBelow,
+    ClearCursors,
+    Save,
+    SaveAs,
+    SaveAll,
+    Find,
+    Replace,
+    GoToLine,
+    GoToPercent,
+    OpenFile,
+    OpenFileUnderCursor,
+    QuickOpen,
+    NewBuffer,
+    CloseBuffer,
+    NextBuffer,
+    PreviousBuffer,
+    Copy,
+    CopyToCommand,
+    FilterThroughCommand,
+    Paste,
+    JoinLines,
+    ToggleBookmark,
+    NextBookmark,
+    PreviousBookmark,
+    ToggleFold,
+    CycleCase,
+    SortLines,
+    RemoveDuplicateLines,
+    ToggleLongLines,
+    ToggleTrailingWhitespace,
+    ToggleIgnoredFiles,
+    ToggleCommentContinuation,
+    ToggleRuler,
+    ToggleWordCount,
+    ReflowParagraph,
+    ToggleSoftWrap,
+    ToggleOverwrite,
+    ToggleAutoSave,
+    ToggleReadOnly,
+    RepeatLastEdit,
+    RecordMacro,
+    PlayMacro,
+    InsertTimestamp,
+    IncrementNumber,
+    DecrementNumber,
+    BlockStart,
+    BlockEnd,
+    ToggleClock,
+    Help,
+    CommandPalette,
+    Quit,
+    ForceQuit,
+}
+
+/// The name each action goes by in `bind.<name>` config lines.
+const ACTION_NAMES: [(&str, Action); 79] = [
+    ("move_up", Action::MoveUp),
+    ("move_down", Action::MoveDown),
+    ("move_left", Action::MoveLeft),
+    ("move_right", Action::MoveRight),
+    ("move_home", Action::MoveHome),
+    ("move_end", Action::MoveEnd),
+    ("select_up", Action::SelectUp),
+    ("select_down", Action::SelectDown),
+    ("select_left", Action::SelectLeft),
+    ("select_right", Action::SelectRight),
+    ("select_home", Action::SelectHome),
+    ("select_end", Action::SelectEnd),
+    ("word_left", Action::WordLeft),
+    ("word_right", Action::WordRight),
+    ("page_up", Action::PageUp),
+    ("page_down", Action::PageDown),
+    ("half_page_up", Action::HalfPageUp),
+    ("half_page_down", Action::HalfPageDown),
+    ("newline", Action::Newline),
+    ("delete_backward", Action::DeleteBackward),
+    ("delete_forward", Action::DeleteForward),
+    ("delete_previous_word", Action::DeletePreviousWord),
+    ("delete_next_word", Action::DeleteNextWord),
+    ("delete_to_line_end", Action::DeleteToLineEnd),
+    ("delete_to_line_start", Action::DeleteToLineStart),
+    ("unindent", Action::Unindent),
+    ("add_cursor_at_next_match", Action::AddCursorAtNextMatch),
+    ("add_cursor_below", Action::AddCursorBelow),
+    ("clear_cursors", Action::ClearCursors),
+    ("save", Action::Save),
+    ("save_as", Action::SaveAs),
+    ("save_all", Action::SaveAll),
+    ("find", Action::Find),
+    ("replace", Action::Replace),
+    ("go_to_line", Action::GoToLine),
+    ("go_to_percent", Action::GoToPercent),
+    ("open_file", Action::OpenFile),
+    ("open_file_under_cursor", Action::OpenFileUnderCursor),
+    ("quick_open", Action::QuickOpen),
+    ("new_buffer", Action::NewBuffer),
+    ("close_buffer", Action::CloseBuffer),
+    ("next_buffer", Action::NextBuffer),
+    ("previous_buffer", Action::PreviousBuffer),
+    ("copy", Action::Copy),
+    ("copy_to_command", Action::CopyToCommand),
+    ("filter_through_command", Action::FilterThroughCommand),
+    ("paste", Action::Paste),
+    ("join_lines", Action::JoinLines),
+    ("toggle_bookmark", Action::ToggleBookmark),
+    ("next_bookmark", Action::NextBookmark),
+    ("previous_bookmark", Action::PreviousBookmark),
+    ("toggle_fold", Action::ToggleFold),
+    ("cycle_case", Action::CycleCase),
+    ("sort_lines", Action::SortLines),
+    ("remove_duplicate_lines", Action::RemoveDuplicateLines),
+    ("toggle_long_lines", Action::ToggleLongLines),
+    (
+        "toggle_trailing_whitespace",
+        Action::ToggleTrailingWhitespace,
+    ),
+    ("toggle_ignored_files", Action::ToggleIgnoredFiles),
+    (
+        "toggle_comment_continuation",
+        Action::ToggleCommentContinuation,
+    ),
+    ("toggle_ruler", Action::ToggleRuler),
+    ("toggle_word_count", Action::ToggleWordCount),
+    ("reflow_paragraph", Action::ReflowParagraph),
+    ("toggle_soft_wrap", Action::ToggleSoftWrap),
+    ("toggle_overwrite", Action::ToggleOverwrite),
+    ("toggle_auto_save", Action::ToggleAutoSave),
+    ("toggle_read_only", Action::ToggleReadOnly),
+    ("repeat_last_edit", Action::RepeatLastEdit),
+    ("record_macro", Action::RecordMacro),
+    ("play_macro", Action::PlayMacro),
+    ("insert_timestamp", Action::InsertTimestamp),
+    ("increment_number", Action::IncrementNumber),
+    ("decrement_number", Action::DecrementNumber),
+    ("block_start", Action::BlockStart),
+    ("block_end", Action::BlockEnd),
+    ("toggle_clock", Action::ToggleClock),
+    ("help", Action::Help),
+    ("command_palette", Action::CommandPalette),
+    ("quit", Action::Quit),
+    ("force_quit", Action::ForceQuit),
+];
+
+const CONTROL_ALT: KeyModifiers = KeyModifiers::CONTROL.union(KeyModifiers::ALT);
+/// Terminals can't tell Ctrl-Shift-<letter> from Ctrl-<letter>, but do send Alt-Shift-<letter>.
+const ALT_SHIFT: KeyModifiers = KeyModifiers::ALT.union(KeyModifiers::SHIFT);
+
+/// The bindings used when the config file doesn't override them.
+const DEFAULT_BINDINGS: [(KeyEvent, Action); 80] = [
+    (key(KeyCode::Up, KeyModifiers::NONE), Action::MoveUp),
+    (key(KeyCode::Down, KeyModifiers::NONE), Action::MoveDown),
+    (key(KeyCode::Left, KeyModifiers::NONE), Action::MoveLeft),
+    (key(KeyCode::Right, KeyModifiers::NONE), Action::MoveRight),
+    (key(KeyCode::Home, KeyModifiers::NONE), Action::MoveHome),
+    (key(KeyCode::End, KeyModifiers::NONE), Action::MoveEnd),
+    (key(KeyCode::Up, KeyModifiers::SHIFT), Action::SelectUp),
+    (key(KeyCode::Down, KeyModifiers::SHIFT), Action::SelectDown),
+    (key(KeyCode::Left, KeyModifiers::SHIFT), Action::SelectLeft),
+    (
+        key(KeyCode::Right, KeyModifiers::SHIFT),
+        Action::SelectRight,
+    ),
+    (key(KeyCode::Home, KeyModifiers::SHIFT), Action::SelectHome),
+    (key(KeyCode::End, KeyModifiers::SHIFT), Action::SelectEnd),
+    (key(KeyCode::Left, KeyModifiers::CONTROL), Action::WordLeft),
+    (
+        key(KeyCode::Right, KeyModifiers::CONTROL),
+        Action::WordRight,
+    ),
+    (key(KeyCode::PageUp, KeyModifiers::NONE), Action::PageUp),
+    (key(KeyCode::PageDown, KeyModifiers::NONE), Action::PageDown),
+    (
+        key(KeyCode::Char('u'), KeyModifiers::CONTROL),
+        Action::HalfPageUp,
+    ),
+    (
+        key(KeyCode::Char('d'), KeyModifiers::CONTROL),
+        Action::HalfPageDown,
+    ),
+    (key(KeyCode::Enter, KeyModifiers::NONE), Action::Newline),
+    (
+        key(KeyCode::Backspace, KeyModifiers::NONE),
+        Action::DeleteBackward,
+    ),
+    (
+        key(KeyCode::Delete, KeyModifiers::NONE),
+        Action::DeleteForward,
+    ),
+    // Terminals send Ctrl-Backspace as ^H, which arrives as Ctrl-H.
+    (
+        key(KeyCode::Char('h'), KeyModifiers::CONTROL),
+        Action::DeletePreviousWord,
+    ),
+    (
+        key(KeyCode::Delete, KeyModifiers::CONTROL),
+        Action::DeleteNextWord,
+    ),
+    (
+        key(KeyCode::Char('k'), KeyModifiers::CONTROL),
+        Action::DeleteToLineEnd,
+    ),
+    (
+        key(KeyCode::Backspace, KeyModifiers::ALT),
+        Action::DeleteToLineStart,
+    ),
+    (key(KeyCode::BackTab, KeyModifiers::NONE), Action::Unindent),
+    (key(KeyCode::BackTab, KeyModifiers::SHIFT), Action::Unindent),
+    (
+        key(KeyCode::Char('d'), KeyModifiers::ALT),
+        Action::AddCursorAtNextMatch,
+    ),
+    (
+        key(KeyCode::Char('n'), KeyModifiers::ALT),
+        Action::AddCursorBelow,
+    ),
+    (key(KeyCode::Esc, KeyModifiers::NONE), Action::ClearCursors),
+    (key(KeyCode::Char('s'), KeyModifiers::CONTROL), Action::Save),
+    (key(KeyCode::Char('S'), ALT_SHIFT), Action::SaveAs),
+    (key(KeyCode::Char('s'), CONTROL_ALT), Action::SaveAll),
+    (key(KeyCode::Char('f'), KeyModifiers::CONTROL), Action::Find),
+    (
+        key(KeyCode::Char('r'), KeyModifiers::CONTROL),
+        Action::Replace,
+    ),
+    (
+        key(KeyCode::Char('g'), KeyModifiers::CONTROL),
+        Action::GoToLine,
+    ),
+    (
+        key(KeyCode::Char('g'), KeyModifiers::ALT),
+        Action::GoToPercent,
+    ),
+    (
+        key(KeyCode::Char('o'), KeyModifiers::CONTROL),
+        Action::OpenFile,
+    ),
+    (
+        key(KeyCode::Enter, KeyModifiers::ALT),
+        Action::OpenFileUnderCursor,
+    ),
+    (
+        key(KeyCode::Char('p'), KeyModifiers::CONTROL),
+        Action::QuickOpen,
+    ),
+    (
+        key(KeyCode::Char('n'), KeyModifiers::CONTROL),
+        Action::NewBuffer,
+    ),
+    (
+        key(KeyCode::Char('w'), KeyModifiers::CONTROL),
+        Action::CloseBuffer,
+    ),
+    (key(KeyCode::Right, KeyModifiers::ALT), Action::NextBuffer),
+    (
+        key(KeyCode::Left, KeyModifiers::ALT),
+        Action::PreviousBuffer,
+    ),
+    (key(KeyCode::Char('c'), KeyModifiers::CONTROL), Action::Copy),
+    (
+        key(KeyCode::Char('y'), KeyModifiers::ALT),
+        Action::CopyToCommand,
+    ),
+    (
+        key(KeyCode::Char('|'), KeyModifiers::ALT),
+        Action::FilterThroughCommand,
+    ),
+    (
+        key(KeyCode::Char('v'), KeyModifiers::CONTROL),
+        Action::Paste,
+    ),
+    (
+        key(KeyCode::Char('j'), KeyModifiers::CONTROL),
+        Action::JoinLines,
+    ),
+    (
+        key(KeyCode::Char('b'), KeyModifiers::ALT),
+        Action::ToggleBookmark,
+    ),
+    (
+        key(KeyCode::Char('j'), KeyModifiers::ALT),
+        Action::NextBookmark,
+    ),
+    (
+        key(KeyCode::Char('k'), KeyModifiers::ALT),
+        Action::PreviousBookmark,
+    ),
+    (
+        key(KeyCode::Char('f'), KeyModifiers::ALT),
+        Action::ToggleFold,
+    ),
+    (
+        key(KeyCode::Char('c'), KeyModifiers::ALT),
+        Action::CycleCase,
+    ),
+    (
+        key(KeyCode::Char('s'), KeyModifiers::ALT),
+        Action::SortLines,
+    ),
+    (
+        key(KeyCode::Char('u'), KeyModifiers::ALT),
+        Action::RemoveDuplicateLines,
+    ),
+    (
+        key(KeyCode::Char('l'), KeyModifiers::ALT),
+        Action::ToggleLongLines,
+    ),
+    (
+        key(KeyCode::Char('e'), KeyModifiers::ALT),
+        Action::ToggleTrailingWhitespace,
+    ),
+    (
+        key(KeyCode::Char('h'), KeyModifiers::ALT),
+        Action::ToggleIgnoredFiles,
+    ),
+    (
+        key(KeyCode::Char('/'), KeyModifiers::ALT),
+        Action::ToggleCommentContinuation,
+    ),
+    (
+        key(KeyCode::Char('r'), KeyModifiers::ALT),
+        Action::ToggleRuler,
+    ),
+    (
+        key(KeyCode::Char('w'), KeyModifiers::ALT),
+        Action::ToggleWordCount,
+    ),
+    (
+        key(KeyCode::Char('q'), KeyModifiers::ALT),
+        Action::ReflowParagraph,
+    ),
+    (
+        key(KeyCode::Char('z'), KeyModifiers::ALT),
+        Action::ToggleSoftWrap,
+    ),
+    (
+        key(KeyCode::Insert, KeyModifiers::NONE),
+        Action::ToggleOverwrite,
+    ),
+    (
+        key(KeyCode::Char('a'), KeyModifiers::ALT),
+        Action::ToggleAutoSave,
+    ),
+    (
+        key(KeyCode::Char('o'), KeyModifiers::ALT),
+        Action::ToggleReadOnly,
+    ),
+    (
+        key(KeyCode::Char('.'), KeyModifiers::ALT),
+        Action::RepeatLastEdit,
+    ),
+    (
+        key(KeyCode::Char('m'), KeyModifiers::ALT),
+        Action::RecordMacro,
+    ),
+    (
+        key(KeyCode::Char('p'), KeyModifiers::ALT),
+        Action::PlayMacro,
+    ),
+    (
+        key(KeyCode::Char('t'), KeyModifiers::ALT),
+        Action::InsertTimestamp,
+    ),
+    (
+        key(KeyCode::Char('a'), KeyModifiers::CONTROL),
+        Action::IncrementNumber,
+    ),
+    (
+        key(KeyCode::Char('x'), KeyModifiers::CONTROL),
+        Action::DecrementNumber,
+    ),
+    (key(KeyCode::Up, KeyModifiers::ALT), Action::BlockStart),
+    (key(KeyCode::Down, KeyModifiers::ALT), Action::BlockEnd),
+    (
+        key(KeyCode::Char('i'), KeyModifiers::ALT),
+        Action::ToggleClock,
+    ),
+    (key(KeyCode::F(1), KeyModifiers::NONE), Action::Help),
+    (
+        key(KeyCode::Char('x'), KeyModifiers::ALT),
+        Action::CommandPalette,
+    ),
+    (key(KeyCode::Char('q'), KeyModifiers::CONTROL), Action::Quit),
+    (key(KeyCode::Char('q'), CONTROL_ALT), Action::ForceQuit),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTION_NAMES
+            .iter()
+            .find(|(action_name, _)| *action_name == name)
+            .map(|(_, action)| *action)
+    }
+}
+
+/// Maps key presses to the actions they trigger.
+pub struct Keymap {
+    bindings: HashMap<KeyEvent, Action>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Self {
+            bindings: DEFAULT_BINDINGS.iter().copied().collect(),
+        }
+    }
+
+    pub fn action(&self, key_event: KeyEvent) -> Option<Action> {
+        self.bindings.get(&key_event).copied()
+    }
+
+    /// Makes `keys` the only keys bound to `action`, taking them over from whatever
+    /// they were bound to before.
+    pub fn bind(&mut self, action: Action, keys: &[KeyEvent]) {
+        self.bindings.retain(|_, bound| *bound != action);
+        for key_event in keys {
+            self.bindings.insert(*key_event, action);
+        }
+    }
+
+    /// Describes the keys bound to `action` like `Ctrl-S / F2`, or `None` if it's unbound.
+    pub fn describe(&self, action: Action) -> Option<String> {
+        let mut keys = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(key_event, _)| Self::describe_key(*key_event))
+            .collect::<Vec<_>>();
+        keys.sort();
+        if keys.is_empty() {
+            None
+        } else {
+            Some(keys.join(" / "))
+        }
+    }
+
+    /// Names a key the way `describe` shows it, like `Ctrl-Alt-S` or `Alt-Left`.
+    pub fn describe_key(key_event: KeyEvent) -> String {
+        let mut text = String::new();
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "Ctrl-"),
+            (KeyModifiers::ALT, "Alt-"),
+            (KeyModifiers::SHIFT, "Shift-"),
+        ] {
+            if key_event.modifiers.contains(modifier) {
+                text.push_str(prefix);
+            }
+        }
+        match key_event.code {
+            KeyCode::Char(' ') => text.push_str("Space"),
+            KeyCode::Char(ch) => text.extend(ch.to_uppercase()),
+            KeyCode::F(number) => text.push_str(&format!("F{}", number)),
+            code => text.push_str(&format!("{:?}", code)),
+        }
+        text
+    }
+
+    /// Parses a key written like `ctrl-alt-s`, `alt-left` or `f3`: any number of
+    /// `ctrl-`, `alt-` and `shift-` prefixes followed by a character or key name.
+    /// Case doesn't matter; `shift-a` is the key terminals send for `A`.
+    pub fn parse_key(text: &str) -> Option<KeyEvent> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text.trim();
+        loop {
+            let (modifier, tail) = match rest.split_once('-') {
+                Some((prefix, tail)) if !tail.is_empty() => (prefix, tail),
+                _ => break,
+            };
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = tail;
+        }
+        let name = rest.to_lowercase();
+        let code = match name.as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "comma" => KeyCode::Char(','),
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    // Terminals send a shifted letter as the uppercase letter with SHIFT set.
+                    (Some(ch), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                        KeyCode::Char(ch.to_ascii_uppercase())
+                    }
+                    (Some(ch), None) => KeyCode::Char(ch.to_ascii_lowercase()),
+                    (Some('f' | 'F'), Some(_)) => KeyCode::F(rest[1..].parse().ok()?),
+                    _ => return None,
+                }
+            }
+        };
+        Some(key(code, modifiers))
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys_as_terminals_send_them() {
+        assert_eq!(
+            Keymap::parse_key("Ctrl-Alt-S"),
+            Some(key(KeyCode::Char('s'), CONTROL_ALT))
+        );
+        assert_eq!(
+            Keymap::parse_key("shift-a"),
+            Some(key(KeyCode::Char('A'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(
+            Keymap::parse_key("Shift-A"),
+            Some(key(KeyCode::Char('A'), KeyModifiers::SHIFT))
+        );
+        assert_eq!(
+            Keymap::parse_key(" alt-pagedown "),
+            Some(key(KeyCode::PageDown, KeyModifiers::ALT))
+        );
+        assert_eq!(
+            Keymap::parse_key("F3"),
+            Some(key(KeyCode::F(3), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            Keymap::parse_key("alt--"),
+            Some(key(KeyCode::Char('-'), KeyModifiers::ALT))
+        );
+        assert_eq!(Keymap::parse_key("hyper-a"), None);
+        assert_eq!(Keymap::parse_key("fx"), None);
+    }
+
+    #[test]
+    fn rebinding_an_action_frees_its_old_keys() {
+        let mut keymap = Keymap::new();
+        let ctrl_f = key(KeyCode::Char('f'), KeyModifiers::CONTROL);
+        let f3 = key(KeyCode::F(3), KeyModifiers::NONE);
+        assert_eq!(keymap.action(ctrl_f), Some(Action::Find));
+        keymap.bind(Action::Find, &[f3]);
+        assert_eq!(keymap.action(f3), Some(Action::Find));
+        assert_eq!(keymap.action(ctrl_f), None);
+        assert_eq!(keymap.describe(Action::Find).as_deref(), Some("F3"));
+    }
+
+    #[test]
+    fn parses_the_description_of_every_default_key() {
+        for (key_event, _) in DEFAULT_BINDINGS {
+            assert_eq!(
+                Keymap::parse_key(&Keymap::describe_key(key_event)),
+                Some(key_event)
+            );
+        }
+    }
+
+    #[test]
+    fn binds_save_as_to_a_key_terminals_can_send() {
+        let keymap = Keymap::new();
+        // What a terminal sends for Alt-Shift-S: ESC followed by an uppercase S.
+        let alt_shift_s = key(KeyCode::Char('S'), ALT_SHIFT);
+        assert_eq!(keymap.action(alt_shift_s), Some(Action::SaveAs));
+        assert_eq!(
+            keymap.describe(Action::SaveAs).as_deref(),
+            Some("Alt-Shift-S")
+        );
+    }
+
+    #[test]
+    fn binds_delete_previous_word_to_a_key_terminals_can_send() {
+        let keymap = Keymap::new();
+        // What a terminal sends for Ctrl-Backspace: ^H, decoded as Ctrl-H.
+        let ctrl_h = key(KeyCode::Char('h'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(ctrl_h), Some(Action::DeletePreviousWord));
+        assert_eq!(
+            keymap.action(key(KeyCode::F(1), KeyModifiers::NONE)),
+            Some(Action::Help)
+        );
+    }
+
+    #[test]
+    fn describes_every_key_bound_to_an_action() {
+        let keymap = Keymap::new();
+        assert_eq!(
+            keymap.describe(Action::Unindent).as_deref(),
+            Some("BackTab / Shift-BackTab")
+        );
+        assert_eq!(
+            keymap.describe(Action::ForceQuit).as_deref(),
+            Some("Ctrl-Alt-Q")
+        );
+        assert_eq!(
+            keymap.describe(Action::CommandPalette).as_deref(),
+            Some("Alt-X")
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::cmp;
+
+/// Largest `old × new` table the line diff fills before giving up on pairing lines.
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LineChange {
+    Added,
+    Modified,
+}
+
+/// Marks each of the `new` lines that was added or changed relative to `old`.
+///
+/// Lines are matched by a longest common subsequence after trimming the common
+/// prefix and suffix. In each run of unmatched lines, as many as were removed count
+/// as modified and the rest as added.
+pub fn line_changes<'a>(
+    old: &[String],
+    new: impl ExactSizeIterator<Item = &'a str> + Clone,
+) -> Vec<Option<LineChange>> {
+    let new_len = new.len();
+    let prefix = old
+        .iter()
+        .zip(new.clone())
+        .take_while(|(old, new)| old.as_str() == *new)
+        .count();
+    let new = new.skip(prefix).collect::<Vec<_>>();
+    let old = &old[prefix..];
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(old, new)| old.as_str() == **new)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    let mut changes = vec![None; new_len];
+    let middle = &mut changes[prefix..prefix + new.len()];
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        middle.iter_mut().enumerate().for_each(|(i, change)| {
+            *change = Some(if i < old.len() {
+                LineChange::Modified
+            } else {
+                LineChange::Added
+            })
+        });
+        return changes;
+    }
+    // `lengths[i][j]` is the longest common subsequence of `old[i..]` and `new[j..]`.
+    let mut lengths = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                cmp::max(lengths[i + 1][j], lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut removed = 0;
+    while j < new.len() {
+        if i < old.len() && old[i] == new[j] {
+            removed = 0;
+            i += 1;
+            j += 1;
+        } else if i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1] {
+            removed += 1;
+            i += 1;
+        } else {
+            middle[j] = Some(if removed > 0 {
+                removed -= 1;
+                LineChange::Modified
+            } else {
+                LineChange::Added
+            });
+            j += 1;
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LineChange::{Added, Modified};
+
+    fn changes(old: &str, new: &str) -> Vec<Option<LineChange>> {
+        let old = old.lines().map(String::from).collect::<Vec<_>>();
+        line_changes(&old, new.lines().collect::<Vec<_>>().into_iter())
+    }
+
+    #[test]
+    fn marks_added_and_modified_lines() {
+        assert_eq!(changes("a\nb\nc", "a\nb\nc"), [None, None, None]);
+        assert_eq!(changes("a\nb\nc", "a\nB\nc"), [None, Some(Modified), None]);
+        assert_eq!(
+            changes("a\nb\nc", "a\nb\nnew\nc"),
+            [None, None, Some(Added), None]
+        );
+        assert_eq!(
+            changes("a\nx\nc", "a\ny\nz\nc"),
+            [None, Some(Modified), Some(Added), None]
+        );
+        assert_eq!(changes("a\nb\nc", "a\nc"), [None, None]);
+        assert_eq!(changes("", "a\nb"), [Some(Added), Some(Added)]);
+    }
+
+    #[test]
+    fn matches_moved_lines_by_longest_common_subsequence() {
+        assert_eq!(
+            changes("a\nb\nc\nd", "b\nc\nd\na"),
+            [None, None, None, Some(Added)]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_positions_past_the_diff_limit() {
+        let old = (0..1001)
+            .map(|i| format!("old {}\n", i))
+            .collect::<String>();
+        let new = (0..1002)
+            .map(|i| format!("new {}\n", i))
+            .collect::<String>();
+        let changes = changes(&old, &new);
+        assert_eq!(changes[..1001], [Some(Modified); 1001]);
+        assert_eq!(changes[1001], Some(Added));
+    }
+}
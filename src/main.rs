@@ -1,35 +1,146 @@
-use crossterm::terminal;
+use std::{io::stdout, panic, process, thread, time::Duration};
+
+use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    style::{Color, Print},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
 use editor::Editor;
 use output::Output;
 
 const VERSION: &str = "0.0.1";
 const TAB_STOP: usize = 8;
 const QUIT_TIMES: u8 = 3;
+/// How long status bar messages stay visible.
+const STATUS_MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Rows of context kept above and below the cursor when scrolling.
+const SCROLLOFF: usize = 0;
+/// Number of lines a single mouse wheel notch scrolls the view by.
+const SCROLL_LINES: usize = 3;
+/// Bytes of each row that syntax highlighting covers; the rest of longer rows is plain.
+const MAX_HIGHLIGHT_LENGTH: usize = 10_000;
+/// Background color used to highlight the active selection.
+const SELECTION_COLOR: Color = Color::DarkBlue;
+/// Render column marked by the ruler, i.e. the first column past the text width.
+const RULER_COLUMN: usize = 80;
+/// Background color of the ruler column.
+const RULER_COLOR: Color = Color::DarkGrey;
+/// Rows whose render is longer than this get the overflow tinted.
+const MAX_LINE_LENGTH: usize = 80;
+/// `strftime` pattern used when inserting a timestamp.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+/// Shell command the selection is piped to by `copy_to_command`, unless configured.
+const COPY_COMMAND: &str = if cfg!(target_os = "macos") {
+    "pbcopy"
+} else {
+    "xclip -selection clipboard"
+};
+/// `strftime` pattern of the clock shown at the right of the status bar.
+const CLOCK_FORMAT: &str = "%H:%M:%S";
+/// Column that paragraphs are reflowed to fit within.
+const WRAP_COLUMN: usize = 80;
+/// Background color of the part of a row past `MAX_LINE_LENGTH`.
+const LONG_LINE_COLOR: Color = Color::DarkRed;
+/// Characters scanned for the bracket matching the one under the cursor before giving up.
+const BRACKET_SCAN_LIMIT: usize = 100_000;
+/// Background color of the bracket under the cursor and its match.
+const BRACKET_COLOR: Color = Color::DarkMagenta;
+/// Background color of the extra carets added for multi-cursor editing.
+const CARET_COLOR: Color = Color::DarkCyan;
+/// Color of the gutter bar on rows added since the file was loaded or saved.
+const ADDED_LINE_COLOR: Color = Color::Green;
+/// Color of the gutter bar on rows changed since the file was loaded or saved.
+const MODIFIED_LINE_COLOR: Color = Color::Yellow;
+/// Color of the gutter marker on bookmarked rows.
+const BOOKMARK_COLOR: Color = Color::Yellow;
+/// Color of the markers at the screen edges of rows that go on past the view.
+const CLIPPED_MARKER_COLOR: Color = Color::DarkGrey;
+/// Color of the summary shown after a folded row.
+const FOLD_COLOR: Color = Color::DarkGrey;
+/// Background color of spaces and tabs at the end of a row.
+const TRAILING_WHITESPACE_COLOR: Color = Color::Red;
+/// Background color of indentation that mixes tabs and spaces.
+const MIXED_INDENT_COLOR: Color = Color::DarkYellow;
+/// How long a read waits for input before the main loop ticks anyway.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Idle time before auto-save writes a dirty buffer, once enabled.
+const AUTO_SAVE_DELAY: Duration = Duration::from_secs(5);
+/// Escape sequence returning the cursor to the terminal's own default shape.
+const DEFAULT_CURSOR_SHAPE: &str = "\x1b[0 q";
+/// How long the screen stays inverted for a visual bell.
+const FLASH_DURATION: Duration = Duration::from_millis(100);
+/// Most candidates listed by a picker overlay.
+const PICKER_RESULTS: usize = 10;
+/// Files larger than this are read lazily as the viewport scrolls.
+const LAZY_LOAD_BYTES: u64 = 64 * 1024 * 1024;
+/// Files larger than this ask for confirmation before being opened.
+const LARGE_FILE_BYTES: u64 = 1024 * 1024 * 1024;
 
+pub mod buffer;
+pub mod config;
 pub mod cursor_controller;
 pub mod editor;
 pub mod editor_contents;
 pub mod editor_rows;
+pub mod fuzzy;
+pub mod gitignore;
+pub mod keymap;
+pub mod line_diff;
 pub mod output;
 pub mod reader;
 pub mod row;
 pub mod search_index;
 pub mod status_message;
 pub mod syntax_highlighting;
+pub mod timestamp;
 
 struct CleanUp;
 
 impl Drop for CleanUp {
     fn drop(&mut self) {
+        // The panic hook has already restored the terminal; clearing now would erase its message.
+        if thread::panicking() {
+            return;
+        }
+        execute!(stdout(), DisableMouseCapture).expect("Unable to disable mouse capture");
+        execute!(stdout(), Print(DEFAULT_CURSOR_SHAPE)).expect("Unable to reset cursor shape");
         terminal::disable_raw_mode().expect("Unable to disable raw mode");
         Output::clear_screen().expect("error");
+        execute!(stdout(), LeaveAlternateScreen).expect("Unable to leave alternate screen");
+    }
+}
+
+/// Restores the terminal before the default hook prints the panic message, so it stays
+/// legible and the shell is usable afterwards.
+fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = execute!(
+            stdout(),
+            DisableMouseCapture,
+            Print(DEFAULT_CURSOR_SHAPE),
+            LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+        default_hook(info);
+    }));
+}
+
+fn main() {
+    install_panic_hook();
+    // `run` restores the terminal before returning, so the error prints on the normal screen.
+    if let Err(err) = run() {
+        eprintln!("pound: {}", err);
+        process::exit(1);
     }
 }
 
-fn main() -> crossterm::Result<()> {
+fn run() -> crossterm::Result<()> {
     let _clean_up = CleanUp;
     terminal::enable_raw_mode()?;
-    let mut editor = Editor::new();
+    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    let mut editor = Editor::new()?;
     while editor.run()? {}
     Ok(())
 }
@@ -1,28 +1,158 @@
 use std::{
-    cmp, env, fs,
+    cmp,
+    collections::{BTreeSet, HashMap},
+    env, fs,
     io::{self, stdout, Write},
-    path::PathBuf,
+    mem,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
 };
 
 use crossterm::{
-    cursor,
-    event::{KeyCode, KeyEvent, KeyModifiers},
-    execute, queue, style,
+    cursor::{self, CursorShape},
+    event::{Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{self, Color, SetBackgroundColor, SetForegroundColor},
     terminal::{self, ClearType},
 };
 
 use crate::{
+    buffer::Buffer,
     cursor_controller::CursorController,
     editor_contents::EditorContents,
     editor_rows::{EditMode, EditorRows, FileType},
+    fuzzy,
+    line_diff::{self, LineChange},
     reader::Reader,
     row::Row,
     search_index::{SearchDirection, SearchIndex},
     status_message::StatusMessage,
-    syntax_highlighting::{HighlightType, RustHighlight, SyntaxHighlight},
-    VERSION,
+    syntax_highlighting::{
+        CssHighlight, GoHighlight, HighlightType, HtmlHighlight, IniHighlight, JsHighlight,
+        PlainTextHighlight, RustHighlight, SqlHighlight, SyntaxHighlight, YamlHighlight,
+    },
+    timestamp, ADDED_LINE_COLOR, AUTO_SAVE_DELAY, BOOKMARK_COLOR, BRACKET_COLOR, CARET_COLOR,
+    CLIPPED_MARKER_COLOR, CLOCK_FORMAT, COPY_COMMAND, FLASH_DURATION, FOLD_COLOR, LARGE_FILE_BYTES,
+    LONG_LINE_COLOR, MAX_LINE_LENGTH, MIXED_INDENT_COLOR, MODIFIED_LINE_COLOR, PICKER_RESULTS,
+    RULER_COLOR, RULER_COLUMN, SELECTION_COLOR, TAB_STOP, TIMESTAMP_FORMAT,
+    TRAILING_WHITESPACE_COLOR, VERSION, WRAP_COLUMN,
 };
 
+#[derive(Copy, Clone)]
+pub enum TextCase {
+    Upper,
+    Lower,
+    Title,
+}
+
+impl TextCase {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            TextCase::Upper => text.to_uppercase(),
+            TextCase::Lower => text.to_lowercase(),
+            TextCase::Title => {
+                let mut word_start = true;
+                text.chars()
+                    .fold(String::with_capacity(text.len()), |mut out, c| {
+                        if word_start {
+                            out.extend(c.to_uppercase());
+                        } else {
+                            out.extend(c.to_lowercase());
+                        }
+                        word_start = !c.is_alphanumeric();
+                        out
+                    })
+            }
+        }
+    }
+
+    /// Picks the case that follows the one `text` is already in, so repeated
+    /// conversions cycle through upper, lower and title case.
+    pub fn next_for(text: &str) -> Self {
+        if text == TextCase::Upper.apply(text) && text != TextCase::Lower.apply(text) {
+            TextCase::Lower
+        } else if text == TextCase::Lower.apply(text) {
+            TextCase::Title
+        } else {
+            TextCase::Upper
+        }
+    }
+}
+
+/// A caret besides the cursor, with the other end of its selection if it has one.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Caret {
+    pub position: (usize, usize),
+    pub anchor: Option<(usize, usize)>,
+}
+
+/// How failed actions are signalled on top of their status message.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Bell {
+    None,
+    /// Rings the terminal bell.
+    Audible,
+    /// Briefly inverts the screen colors.
+    Visual,
+}
+
+/// Terminal cursor shape used in each editing mode.
+#[derive(Copy, Clone, PartialEq)]
+pub struct CursorShapes {
+    pub insert: CursorShape,
+    pub overwrite: CursorShape,
+    pub read_only: CursorShape,
+}
+
+impl CursorShapes {
+    pub fn new() -> Self {
+        Self {
+            insert: CursorShape::Line,
+            overwrite: CursorShape::Block,
+            read_only: CursorShape::Block,
+        }
+    }
+}
+
+impl Default for CursorShapes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A repeatable editing operation, recorded so the last one can be re-applied.
+#[derive(Copy, Clone)]
+pub enum EditCommand {
+    InsertChar(char),
+    InsertNewline,
+    DeleteBackward,
+    DeleteForward,
+    JoinLines,
+    Paste,
+    Indent,
+    Unindent,
+    /// Adds the amount to the number under or after the cursor.
+    Increment(i64),
+    /// Deletes from the cursor to the end (`Forward`) or start of the row.
+    DeleteToLineEdge(SearchDirection),
+    /// Deletes up to the next word boundary after (`Forward`) or before the cursor.
+    DeleteWord(SearchDirection),
+}
+
+pub type PromptCallback<'a> = dyn Fn(&mut Output, &str, KeyCode) + 'a;
+
+/// What a prompt does with keys beyond plain text editing.
+#[derive(Copy, Clone, PartialEq)]
+enum PromptKind {
+    Text,
+    /// Completes the input against the filesystem on Tab.
+    Path,
+    /// Hands plain Left/Right to the callback, leaving Alt-Left/Right to move the caret.
+    Search,
+}
+
 pub struct Output {
     pub win_size: (usize, usize),
     pub editor_contents: EditorContents,
@@ -32,110 +162,390 @@ pub struct Output {
     pub dirty: u64,
     pub search_index: SearchIndex,
     pub syntax_highlight: Option<Box<dyn SyntaxHighlight>>,
+    pub show_word_count: bool,
+    pub show_clock: bool,
+    word_count: Option<(usize, usize)>,
+    /// Per-row changes against the baseline, recomputed on the first draw after an edit.
+    line_changes: Option<Vec<Option<LineChange>>>,
+    pub clipboard: Option<String>,
+    pub last_edit: Option<EditCommand>,
+    /// Indices of bookmarked rows.
+    pub bookmarks: BTreeSet<usize>,
+    /// Render column to draw a vertical ruler on, if any.
+    pub ruler: Option<usize>,
+    /// Column that `toggle_ruler` turns the ruler on at.
+    pub ruler_column: usize,
+    /// Render length past which rows are tinted as too long, if any.
+    pub max_line_length: Option<usize>,
+    /// Length that `toggle_long_lines` starts tinting rows past.
+    pub long_line_length: usize,
+    /// Whether directory listings and quick-open include files `.gitignore` excludes.
+    pub show_ignored: bool,
+    /// Whether spaces and tabs at the end of rows are tinted.
+    pub show_trailing_whitespace: bool,
+    /// Whether a new row opened inside a line comment starts with the comment prefix.
+    pub continue_comments: bool,
+    /// Open buffers other than the one shown, in order.
+    pub buffers: Vec<Buffer>,
+    /// Position of the shown buffer among all open buffers.
+    pub current_buffer: usize,
+    /// `strftime` pattern used by `insert_timestamp`.
+    pub timestamp_format: String,
+    /// Column that `reflow_paragraph` wraps rows at.
+    pub wrap_column: usize,
+    /// Whether typed characters replace the one under the cursor instead of being inserted.
+    pub overwrite: bool,
+    /// Idle time after which a dirty, named buffer is saved, if auto-save is on.
+    pub auto_save_after: Option<Duration>,
+    /// Extra text shown after the active prompt until the next key.
+    pub prompt_note: Option<String>,
+    /// Column of the caret in the message bar while a prompt is active.
+    pub prompt_caret: Option<usize>,
+    /// Previously entered inputs for each prompt, keyed by the prompt message.
+    pub prompt_history: HashMap<String, Vec<String>>,
+    /// Lines listed over the bottom of the text area while a picker prompt is open.
+    pub overlay: Vec<String>,
+    /// Index of the highlighted overlay line.
+    pub overlay_selected: usize,
+    pub bell: Bell,
+    /// Carets besides the cursor that typing and deleting also apply at.
+    pub extra_carets: Vec<Caret>,
+    /// Files larger than this are only opened after confirming.
+    pub large_file_bytes: u64,
+    pub cursor_shapes: CursorShapes,
+    /// Shell command the selection is piped to for the system clipboard.
+    pub copy_command: String,
+    /// Shape last sent to the terminal, so it's only re-sent when the mode changes.
+    shown_cursor_shape: Option<CursorShape>,
 }
 
 impl Output {
     pub fn select_syntax(extension: &str) -> Option<Box<dyn SyntaxHighlight>> {
-        let list: Vec<Box<dyn SyntaxHighlight>> = vec![Box::new(RustHighlight::new())];
+        let list: Vec<Box<dyn SyntaxHighlight>> = vec![
+            Box::new(RustHighlight::new()),
+            Box::new(YamlHighlight::new()),
+            Box::new(GoHighlight::new()),
+            Box::new(JsHighlight::new()),
+            Box::new(CssHighlight::new()),
+            Box::new(HtmlHighlight::new()),
+            Box::new(SqlHighlight::new()),
+            Box::new(IniHighlight::new()),
+            Box::new(PlainTextHighlight::new()),
+        ];
+        // Files with an unknown extension are reported as text.
         list.into_iter()
             .find(|it| it.extensions().contains(&extension))
+            .or_else(|| Some(Box::new(PlainTextHighlight::new())))
     }
 
-    pub fn new() -> Self {
-        let win_size = terminal::size()
-            .map(|(x, y)| (x as usize, y as usize - 2))
-            .unwrap();
+    pub fn new() -> crossterm::Result<Self> {
+        let win_size =
+            terminal::size().map(|(x, y)| (x as usize, (y as usize).saturating_sub(2)))?;
+        Ok(Self::with_size(win_size))
+    }
+
+    /// Opens the files named on the command line, showing the first.
+    pub fn open_args(&mut self) -> io::Result<()> {
+        for arg in env::args().skip(1) {
+            let (file, location) = Self::parse_location(&arg);
+            self.load_file(file)?;
+            if let Some((line, column)) = location {
+                self.jump_to(line, column);
+            }
+        }
+        self.switch_buffer(0);
+        Ok(())
+    }
+
+    /// Creates an empty editor with a text area of `win_size` columns and rows,
+    /// without querying the terminal or opening the command line's files.
+    pub fn with_size(win_size: (usize, usize)) -> Self {
         let syntax_highlight = None; // modify
-        let mut new_self = Self {
+        Self {
             win_size,
             editor_contents: EditorContents::new(),
             cursor_controller: CursorController::new(win_size),
             editor_rows: EditorRows::new(),
-            status_message: StatusMessage::new("HELP: Ctrl-h".into()),
+            status_message: StatusMessage::new(String::new()),
             dirty: 0,
             search_index: SearchIndex::new(),
             syntax_highlight,
-        };
-
-        match env::args().nth(1) {
-            Some(file) => new_self
-                .open_file(file.into())
-                .expect("Failed to open file"),
-            None => (),
+            show_word_count: false,
+            show_clock: false,
+            word_count: None,
+            line_changes: None,
+            clipboard: None,
+            last_edit: None,
+            bookmarks: BTreeSet::new(),
+            ruler: None,
+            ruler_column: RULER_COLUMN,
+            max_line_length: None,
+            long_line_length: MAX_LINE_LENGTH,
+            show_ignored: false,
+            show_trailing_whitespace: false,
+            continue_comments: true,
+            buffers: Vec::new(),
+            current_buffer: 0,
+            timestamp_format: TIMESTAMP_FORMAT.into(),
+            wrap_column: WRAP_COLUMN,
+            overwrite: false,
+            auto_save_after: None,
+            prompt_note: None,
+            prompt_caret: None,
+            prompt_history: HashMap::new(),
+            overlay: Vec::new(),
+            overlay_selected: 0,
+            bell: Bell::None,
+            extra_carets: Vec::new(),
+            large_file_bytes: LARGE_FILE_BYTES,
+            cursor_shapes: CursorShapes::new(),
+            copy_command: COPY_COMMAND.into(),
+            shown_cursor_shape: None,
         }
+    }
 
-        new_self
+    /// Splits a `path:line` or `path:line:column` argument, as printed by grep and
+    /// compilers, into the path and 1-based position. Existing paths are taken as is.
+    fn parse_location(arg: &str) -> (PathBuf, Option<(usize, usize)>) {
+        if !Path::new(arg).exists() {
+            let mut parts = arg.rsplitn(3, ':');
+            if let (Some(column), Some(line), Some(path)) =
+                (parts.next(), parts.next(), parts.next())
+            {
+                if let (Ok(line), Ok(column)) = (line.parse(), column.parse()) {
+                    return (path.into(), Some((line, column)));
+                }
+            }
+            if let Some((path, line)) = arg.rsplit_once(':') {
+                if let Ok(line) = line.parse() {
+                    return (path.into(), Some((line, 1)));
+                }
+            }
+        }
+        (arg.into(), None)
     }
 
     pub fn prompt(&mut self, message: &str) -> Option<String> {
         self.prompt_callback(message, None)
     }
 
+    /// Prompts for a path, completing it against the filesystem on Tab.
+    pub fn prompt_path(&mut self, message: &str) -> Option<String> {
+        self.prompt_with(message, None, PromptKind::Path)
+    }
+
     pub fn prompt_callback(
         &mut self,
         message: &str,
-        callback: Option<&dyn Fn(&mut Output, &str, KeyCode)>,
+        callback: Option<&PromptCallback<'_>>,
+    ) -> Option<String> {
+        self.prompt_with(message, callback, PromptKind::Text)
+    }
+
+    /// Returns what `input` could complete to, with directories ending in '/'.
+    fn path_completions(input: &str) -> Vec<String> {
+        let (dir, prefix) = match input.rfind('/') {
+            Some(i) => input.split_at(i + 1),
+            None => ("", input),
+        };
+        let entries = match fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let mut completions = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if !name.starts_with(prefix) || (prefix.is_empty() && name.starts_with('.')) {
+                    return None;
+                }
+                let suffix = if entry.path().is_dir() { "/" } else { "" };
+                Some(format!("{}{}{}", dir, name, suffix))
+            })
+            .collect::<Vec<_>>();
+        completions.sort();
+        completions
+    }
+
+    /// Completes `input` to the longest prefix shared by its completions, listing them
+    /// in the overlay when there's more than one.
+    fn complete_path(&mut self, input: &mut String) {
+        let completions = Output::path_completions(input);
+        let common = match completions.first() {
+            Some(first) => completions
+                .iter()
+                .fold(first.as_str(), |common, completion| {
+                    let len = common
+                        .char_indices()
+                        .zip(completion.chars())
+                        .find(|((_, a), b)| a != b)
+                        .map_or(cmp::min(common.len(), completion.len()), |((i, _), _)| i);
+                    &common[..len]
+                }),
+            None => return,
+        };
+        if common.len() > input.len() {
+            *input = common.into();
+        }
+        if completions.len() > 1 {
+            self.overlay = completions.into_iter().take(PICKER_RESULTS).collect();
+            self.overlay_selected = usize::MAX;
+        }
+    }
+
+    fn prompt_byte_index(input: &str, caret: usize) -> usize {
+        input
+            .char_indices()
+            .nth(caret)
+            .map_or(input.len(), |(index, _)| index)
+    }
+
+    fn prompt_with(
+        &mut self,
+        message: &str,
+        callback: Option<&PromptCallback<'_>>,
+        kind: PromptKind,
     ) -> Option<String> {
         let mut input = String::with_capacity(32);
+        // Character index in `input` where typing inserts.
+        let mut caret = 0;
+        let caret_start = message
+            .find("{}")
+            .map_or(0, |at| message[..at].chars().count());
+        let mut history = self.prompt_history.remove(message).unwrap_or_default();
+        // Position in `history` of the entry shown, while the user is browsing it.
+        let mut history_index: Option<usize> = None;
         loop {
-            self.status_message
-                .set_message(message.replace("{}", &input));
-            match self.refresh_screen() {
-                Ok(_) => {}
-                Err(_) => return None,
+            let mut prompt = message.replace("{}", &input);
+            if let Some(note) = self.prompt_note.take() {
+                prompt.push_str(" -- ");
+                prompt.push_str(&note);
+            }
+            self.status_message.set_message(prompt);
+            self.prompt_caret = Some(caret_start + caret);
+            let key_event = match self.refresh_screen().and_then(|_| self.read_key()) {
+                Ok(key_event) => key_event,
+                Err(_) => {
+                    self.prompt_caret = None;
+                    return None;
+                }
+            };
+            if kind == PromptKind::Path {
+                self.overlay.clear();
             }
-            let key_event = Reader.read_key().unwrap();
             match key_event {
                 KeyEvent {
                     code: KeyCode::Enter,
                     modifiers: KeyModifiers::NONE,
-                } => {
-                    if !input.is_empty() {
-                        self.status_message.set_message(String::new());
-                        match callback {
-                            Some(c) => c(self, &input, KeyCode::Enter),
-                            None => {}
-                        }
-                        // $callback(output, &input, KeyCode::Enter);
-                        break;
+                } if !input.is_empty() => {
+                    self.status_message.set_message(String::new());
+                    if let Some(c) = callback {
+                        c(self, &input, KeyCode::Enter)
                     }
+                    // $callback(output, &input, KeyCode::Enter);
+                    break;
                 }
                 KeyEvent {
                     code: KeyCode::Esc, ..
                 } => {
                     self.status_message.set_message(String::new());
                     input.clear();
-                    match callback {
-                        Some(c) => c(self, &input, KeyCode::Esc),
-                        None => {}
+                    if let Some(c) = callback {
+                        c(self, &input, KeyCode::Esc)
                     }
                     // $callback(output, &input, KeyCode::Esc);
                     break;
                 }
                 KeyEvent {
-                    code: KeyCode::Backspace | KeyCode::Delete,
+                    code: code @ (KeyCode::Up | KeyCode::Down),
+                    modifiers: KeyModifiers::NONE,
+                } if !history.is_empty()
+                    && (history_index.is_some() || (input.is_empty() && code == KeyCode::Up)) =>
+                {
+                    let index = match history_index {
+                        None => history.len() - 1,
+                        Some(index) if code == KeyCode::Up => index.saturating_sub(1),
+                        Some(index) => cmp::min(index + 1, history.len() - 1),
+                    };
+                    history_index = Some(index);
+                    input = history[index].clone();
+                    caret = input.chars().count();
+                    if let Some(c) = callback {
+                        c(self, &input, KeyCode::Null)
+                    }
+                    continue;
+                }
+                KeyEvent {
+                    code: KeyCode::Left | KeyCode::Right,
+                    modifiers: KeyModifiers::NONE,
+                } if kind == PromptKind::Search => {}
+                KeyEvent {
+                    code: code @ (KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End),
+                    modifiers: KeyModifiers::NONE | KeyModifiers::ALT,
+                } => {
+                    caret = match code {
+                        KeyCode::Left => caret.saturating_sub(1),
+                        KeyCode::Right => cmp::min(caret + 1, input.chars().count()),
+                        KeyCode::Home => 0,
+                        _ => input.chars().count(),
+                    };
+                    continue;
+                }
+                KeyEvent {
+                    code: KeyCode::Backspace,
                     modifiers: KeyModifiers::NONE,
                 } => {
-                    input.pop();
+                    history_index = None;
+                    if caret > 0 {
+                        caret -= 1;
+                        input.remove(Self::prompt_byte_index(&input, caret));
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Delete,
+                    modifiers: KeyModifiers::NONE,
+                } => {
+                    history_index = None;
+                    if caret < input.chars().count() {
+                        input.remove(Self::prompt_byte_index(&input, caret));
+                    }
+                }
+                KeyEvent {
+                    code: KeyCode::Tab,
+                    modifiers: KeyModifiers::NONE,
+                } if kind == PromptKind::Path => {
+                    history_index = None;
+                    self.complete_path(&mut input);
+                    caret = input.chars().count();
                 }
                 KeyEvent {
                     code: code @ (KeyCode::Char(..) | KeyCode::Tab),
                     modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
                 } => {
-                    input.push(match code {
-                        KeyCode::Tab => '\t',
-                        KeyCode::Char(ch) => ch,
-                        _ => unreachable!(),
-                    });
+                    history_index = None;
+                    input.insert(
+                        Self::prompt_byte_index(&input, caret),
+                        match code {
+                            KeyCode::Tab => '\t',
+                            KeyCode::Char(ch) => ch,
+                            _ => unreachable!(),
+                        },
+                    );
+                    caret += 1;
                 }
                 _ => {}
             }
-            match callback {
-                Some(c) => c(self, &input, key_event.code),
-                None => {}
+            if let Some(c) = callback {
+                c(self, &input, key_event.code)
             }
             // $callback(output, &input, key_event.code);
         }
+        self.prompt_caret = None;
+        if !input.is_empty() {
+            history.retain(|entry| *entry != input);
+            history.push(input.clone());
+        }
+        self.prompt_history.insert(message.into(), history);
         if input.is_empty() {
             None
         } else {
@@ -144,86 +554,462 @@ impl Output {
     }
 
     pub fn save_file(&mut self) -> crossterm::Result<()> {
-        if matches!(self.editor_rows.filename, None) {
-            let prompt = self
-                .prompt("Save as : {} (ESC to cancel)")
-                .map(|it| it.into());
-            if prompt.is_none() {
+        if self.editor_rows.filename.is_none() {
+            return self.save_as();
+        }
+        self.write_file()
+    }
+
+    /// Prompts for a path and writes the buffer there, leaving any file it was
+    /// loaded from as it is. The syntax is re-selected for the new extension.
+    pub fn save_as(&mut self) -> crossterm::Result<()> {
+        let prompt: Option<PathBuf> = self
+            .prompt_path("Save as : {} (ESC to cancel)")
+            .map(|it| it.into());
+        match &prompt {
+            None => {
                 self.status_message.set_message("Save Aborted".into());
                 return Ok(());
             }
-            /* add the following */
-            prompt
-                .as_ref()
-                .and_then(|path: &PathBuf| path.extension())
-                .and_then(|ext| ext.to_str())
-                .map(|ext| {
-                    Output::select_syntax(ext).map(|syntax| {
-                        let highlight = self.syntax_highlight.insert(syntax);
-                        for i in 0..self.editor_rows.number_of_rows() {
-                            highlight.update_syntax(i, &mut self.editor_rows.row_contents)
-                        }
-                    })
-                });
+            // Keep the current name, so the buffer isn't left pointing at the directory.
+            Some(path) if path.is_dir() => {
+                self.status_message
+                    .set_message("Cannot save: path is a directory".into());
+                return Ok(());
+            }
+            Some(_) => {}
+        }
+        // Rows not read yet come from the original file, so read them before renaming.
+        self.editor_rows.load_all(self.syntax_highlight.as_deref());
+        /* add the following */
+        prompt
+            .as_ref()
+            .and_then(|path| path.extension())
+            .and_then(|ext| ext.to_str())
+            .map(|ext| {
+                Output::select_syntax(ext).map(|syntax| {
+                    let highlight = self.syntax_highlight.insert(syntax);
+                    for i in 0..self.editor_rows.number_of_rows() {
+                        highlight.update_syntax(i, &mut self.editor_rows.row_contents)
+                    }
+                })
+            });
+
+        self.editor_rows.filename = prompt;
+        self.write_file()
+    }
 
-            self.editor_rows.filename = prompt
+    /// Writes the buffer to its filename, offering to create a missing directory.
+    fn write_file(&mut self) -> crossterm::Result<()> {
+        self.editor_rows.load_all(self.syntax_highlight.as_deref());
+        let missing_parent = self
+            .editor_rows
+            .filename
+            .as_ref()
+            .and_then(|path| path.parent())
+            .filter(|parent| !parent.as_os_str().is_empty() && !parent.exists())
+            .map(PathBuf::from);
+        if let Some(parent) = missing_parent {
+            let answer = self.prompt("Cannot save: directory does not exist, create it? (y/n) {}");
+            if answer.is_none_or(|answer| answer.to_lowercase() != "y") {
+                self.status_message
+                    .set_message("Cannot save: directory does not exist".into());
+                return Ok(());
+            }
+            if let Err(err) = fs::create_dir_all(&parent) {
+                self.status_message
+                    .set_message(format!("Cannot create directory: {}", err));
+                return Ok(());
+            }
+        }
+        match self.editor_rows.save() {
+            Ok(report) => {
+                self.status_message.set_message(format!(
+                    "{} {}",
+                    if report.created { "Created" } else { "Wrote" },
+                    report
+                ));
+                self.dirty = 0;
+                self.line_changes = None;
+            }
+            Err(err) => self
+                .status_message
+                .set_message(format!("Cannot save: {}", err)),
         }
-        self.editor_rows.save().map(|len| {
-            self.status_message
-                .set_message(format!("{} bytes written to disk", len));
-            self.dirty = 0
-        })?;
 
         Ok(())
     }
 
-    pub fn open_file(&mut self, open_file: PathBuf) -> crossterm::Result<()> {
+    /// Writes the buffer to disk without prompting, if it has a filename.
+    pub fn auto_save(&mut self) {
+        if self.editor_rows.filename.is_none() {
+            return;
+        }
+        self.editor_rows.load_all(self.syntax_highlight.as_deref());
+        match self.editor_rows.save() {
+            Ok(report) => {
+                self.status_message
+                    .set_message(format!("Auto-saved {}", report));
+                self.dirty = 0;
+                self.line_changes = None;
+            }
+            Err(err) => self
+                .status_message
+                .set_message(format!("Auto-save failed: {}", err)),
+        }
+    }
+
+    /// Adopts a new terminal size of `columns` by `rows`, keeping two rows for the status bar
+    /// and message line.
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        self.win_size = (columns as usize, (rows as usize).saturating_sub(2));
+        self.cursor_controller.screen_columns = self.win_size.0;
+        self.cursor_controller.screen_rows = self.win_size.1;
+        for buffer in &mut self.buffers {
+            buffer.cursor_controller.screen_columns = self.win_size.0;
+            buffer.cursor_controller.screen_rows = self.win_size.1;
+        }
+    }
+
+    /// Waits for a key press, redrawing whenever the terminal is resized in the meantime.
+    fn read_key(&mut self) -> crossterm::Result<KeyEvent> {
+        loop {
+            match Reader.read_event()? {
+                Some(Event::Key(key_event)) => return Ok(key_event),
+                Some(Event::Resize(columns, rows)) => {
+                    self.resize(columns, rows);
+                    self.refresh_screen()?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn toggle_soft_wrap(&mut self) {
+        self.cursor_controller.soft_wrap = !self.cursor_controller.soft_wrap;
+    }
+
+    pub fn toggle_overwrite(&mut self) {
+        self.overwrite = !self.overwrite;
+    }
+
+    pub fn toggle_auto_save(&mut self) {
+        self.auto_save_after = match self.auto_save_after {
+            Some(_) => None,
+            None => Some(AUTO_SAVE_DELAY),
+        };
+        self.status_message.set_message(format!(
+            "Auto-save {}",
+            if self.auto_save_after.is_some() {
+                "on"
+            } else {
+                "off"
+            }
+        ));
+    }
+
+    /// Offers to save unsaved changes, returning `false` if the prompt was cancelled or the
+    /// save didn't go through. A cancelled prompt shows `aborted`; a failed save leaves its
+    /// own message.
+    fn save_changes_prompt(&mut self, aborted: &str) -> crossterm::Result<bool> {
         if self.dirty != 0 {
             let save_prompt = self.prompt("You have unsaved changes, save? (y/n) {}");
             match save_prompt {
                 Some(answer) => {
                     if answer.to_lowercase() == "y" {
                         self.save_file()?;
+                        if self.dirty != 0 {
+                            return Ok(false);
+                        }
                     }
                 }
-                None => self.status_message.set_message("Open File Aborted".into()),
+                None => {
+                    self.status_message.set_message(aborted.into());
+                    return Ok(false);
+                }
             }
         }
+        Ok(true)
+    }
 
-        if open_file.is_file() {
-            self.editor_rows = EditorRows::from_file(open_file, &mut self.syntax_highlight);
-        } else if open_file.is_dir() {
-            let mut rows = vec![];
+    /// Moves the shown buffer's state out, leaving an empty buffer in its place.
+    fn take_buffer(&mut self) -> Buffer {
+        let mut empty = Buffer::new(self.win_size);
+        empty.cursor_controller.scrolloff = self.cursor_controller.scrolloff;
+        self.search_index.reset();
+        self.word_count = None;
+        self.line_changes = None;
+        self.extra_carets.clear();
+        Buffer {
+            editor_rows: mem::replace(&mut self.editor_rows, empty.editor_rows),
+            cursor_controller: mem::replace(&mut self.cursor_controller, empty.cursor_controller),
+            dirty: mem::replace(&mut self.dirty, empty.dirty),
+            syntax_highlight: mem::replace(&mut self.syntax_highlight, empty.syntax_highlight),
+            bookmarks: mem::replace(&mut self.bookmarks, empty.bookmarks),
+        }
+    }
 
-            for file in fs::read_dir(open_file).unwrap() {
-                let mut row =
-                    Row::new(file.unwrap().path().to_str().unwrap().into(), String::new());
+    fn show_buffer(&mut self, buffer: Buffer) {
+        self.search_index.reset();
+        self.word_count = None;
+        self.line_changes = None;
+        self.extra_carets.clear();
+        self.editor_rows = buffer.editor_rows;
+        // Scrolloff is a setting of the editor, not of a buffer.
+        let scrolloff = self.cursor_controller.scrolloff;
+        self.cursor_controller = buffer.cursor_controller;
+        self.cursor_controller.scrolloff = scrolloff;
+        self.dirty = buffer.dirty;
+        self.syntax_highlight = buffer.syntax_highlight;
+        self.bookmarks = buffer.bookmarks;
+    }
 
-                EditorRows::render_row(&mut row);
+    pub fn number_of_buffers(&self) -> usize {
+        self.buffers.len() + 1
+    }
 
-                rows.push(row);
-            }
+    pub fn any_dirty(&self) -> bool {
+        self.dirty > 0 || self.buffers.iter().any(|buffer| buffer.dirty > 0)
+    }
+
+    pub fn switch_buffer(&mut self, index: usize) {
+        if index == self.current_buffer || index >= self.number_of_buffers() {
+            return;
+        }
+        let current = self.take_buffer();
+        self.buffers.insert(self.current_buffer, current);
+        let next = self.buffers.remove(index);
+        self.show_buffer(next);
+        self.current_buffer = index;
+    }
+
+    pub fn cycle_buffer(&mut self, direction: SearchDirection) {
+        let count = self.number_of_buffers();
+        self.switch_buffer(match direction {
+            SearchDirection::Forward => (self.current_buffer + 1) % count,
+            SearchDirection::Backward => (self.current_buffer + count - 1) % count,
+        });
+    }
 
-            let editor_rows = EditorRows {
-                row_contents: rows,
-                filename: None,
-                file_type: FileType::DIR,
-                edit_mode: EditMode::READONLY,
+    /// Shows a new empty buffer after the current one, keeping the current one open.
+    fn push_buffer(&mut self) {
+        let current = self.take_buffer();
+        self.buffers.insert(self.current_buffer, current);
+        self.current_buffer += 1;
+    }
+
+    /// Drops the shown buffer and shows the one after it, or the one before if it was last.
+    pub fn close_buffer(&mut self) -> crossterm::Result<()> {
+        if !self.save_changes_prompt("Close Buffer Aborted")? {
+            return Ok(());
+        }
+        if self.buffers.is_empty() {
+            self.show_buffer(Buffer::new(self.win_size));
+            return Ok(());
+        }
+        if self.current_buffer == self.buffers.len() {
+            self.current_buffer -= 1;
+        }
+        let next = self.buffers.remove(self.current_buffer);
+        self.show_buffer(next);
+        Ok(())
+    }
+
+    pub fn new_buffer(&mut self) -> crossterm::Result<()> {
+        if self.save_changes_prompt("New Buffer Aborted")? {
+            self.show_buffer(Buffer::new(self.win_size));
+        }
+        Ok(())
+    }
+
+    /// Opens `open_file` in a buffer, showing why in the message bar if it can't be read.
+    pub fn open_file(&mut self, open_file: PathBuf) -> crossterm::Result<()> {
+        if let Err(err) = self.load_file(open_file) {
+            self.status_message
+                .set_message(format!("Cannot open file: {}", err));
+        }
+        Ok(())
+    }
+
+    /// Opens the path under the cursor, relative to the current file's directory.
+    /// A trailing `:line[:column]` jumps there, and a bare name like `mod output`'s
+    /// also tries the current file's extension.
+    pub fn open_file_under_cursor(&mut self) -> crossterm::Result<()> {
+        if self.editor_rows.file_type == FileType::DIR {
+            if let Some(entry) = self.editor_rows.get_entry(self.cursor_controller.cursor_y) {
+                self.open_file(entry.clone())?;
+            }
+            return Ok(());
+        }
+        let (start, end) =
+            match self.token_at_cursor(|c| c.is_alphanumeric() || "/\\._-~+:".contains(c)) {
+                Some(range) => range,
+                None => {
+                    self.alert("No path under the cursor".into());
+                    return Ok(());
+                }
             };
+        let row = self
+            .editor_rows
+            .get_editor_row(self.cursor_controller.cursor_y);
+        let token = row.row_content[row.byte_index(start)..row.byte_index(end)]
+            .trim_end_matches(['.', ':'])
+            .to_string();
+        let current = self.editor_rows.filename.clone();
+        let base = current
+            .as_ref()
+            .and_then(|file| file.parent())
+            .map_or_else(PathBuf::new, Path::to_path_buf);
+        let (path, location) = Self::parse_location(&token);
+        let path = match path.strip_prefix("~") {
+            Ok(rest) => {
+                env::var_os("HOME").map_or_else(|| path.clone(), |home| Path::new(&home).join(rest))
+            }
+            Err(_) => base.join(&path),
+        };
+        let with_extension = current
+            .as_ref()
+            .and_then(|file| file.extension())
+            .filter(|_| path.extension().is_none())
+            .map(|extension| path.with_extension(extension));
+        let path = match with_extension {
+            Some(candidate) if !path.exists() && candidate.exists() => candidate,
+            _ => path,
+        };
+        if !path.exists() {
+            self.alert(format!("No such file: {}", path.display()));
+            return Ok(());
+        }
+        self.open_file(path)?;
+        if let Some((line, column)) = location {
+            self.jump_to(line, column);
+        }
+        Ok(())
+    }
+
+    /// Formats a byte count with one decimal in the largest unit it reaches.
+    fn format_size(bytes: u64) -> String {
+        const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+        let mut size = bytes as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit + 1 < UNITS.len() {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} B", bytes)
+        } else {
+            format!("{:.1} {}", size, UNITS[unit])
+        }
+    }
 
+    /// Opens `open_file` in a buffer, returning an error naming it if it can't be read.
+    pub fn load_file(&mut self, open_file: PathBuf) -> io::Result<()> {
+        // Edit what a symlink points to so saving writes the target. A dangling link
+        // is an error rather than a new file, which saving would create behind it.
+        let open_file = match fs::symlink_metadata(&open_file) {
+            Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(&open_file)
+                .map_err(|err| {
+                    io::Error::new(
+                        err.kind(),
+                        format!("{}: broken symbolic link", open_file.display()),
+                    )
+                })?,
+            _ => open_file,
+        };
+        let size = fs::metadata(&open_file).map_or(0, |metadata| metadata.len());
+        if open_file.is_file() && size > self.large_file_bytes {
+            let answer = self.prompt(&format!(
+                "File is {}, open anyway? (y/n) {{}}",
+                Self::format_size(size)
+            ));
+            if answer.map(|answer| answer.to_lowercase()).as_deref() != Some("y") {
+                self.status_message.set_message("Open cancelled".into());
+                return Ok(());
+            }
+        }
+        // Read directories and files up front so a failure leaves the current buffer untouched.
+        let dir_rows = if open_file.is_dir() {
+            match EditorRows::from_dir(open_file.clone(), self.show_ignored) {
+                Ok(editor_rows) => Some(editor_rows),
+                Err(err) => {
+                    self.status_message
+                        .set_message(format!("Cannot read directory: {}", err));
+                    return Ok(());
+                }
+            }
+        } else {
+            None
+        };
+        let mut syntax_highlight = None;
+        let file_rows = if open_file.is_file() {
+            let editor_rows = EditorRows::from_file(open_file.clone(), &mut syntax_highlight)
+                .map_err(|err| {
+                    io::Error::new(err.kind(), format!("{}: {}", open_file.display(), err))
+                })?;
+            Some(editor_rows)
+        } else {
+            None
+        };
+        // Directory listings and untouched empty buffers are replaced rather than kept.
+        let replaceable = matches!(self.editor_rows.file_type, FileType::DIR)
+            || (self.dirty == 0
+                && self.editor_rows.filename.is_none()
+                && self.editor_rows.number_of_rows() == 0);
+        if replaceable {
+            self.show_buffer(Buffer::new(self.win_size));
+        } else {
+            self.push_buffer();
+        }
+        if let Some(editor_rows) = dir_rows.or(file_rows) {
             self.editor_rows = editor_rows;
+            self.syntax_highlight = syntax_highlight;
         } else {
             self.editor_rows = EditorRows {
-                row_contents: Vec::new(),
                 filename: Some(open_file),
-                file_type: FileType::FILE,
-                edit_mode: EditMode::NORMAL,
+                ..EditorRows::new()
             }
         }
 
         Ok(())
     }
 
+    /// Saves every dirty buffer, prompting for a name only for unnamed ones.
+    pub fn save_all(&mut self) -> crossterm::Result<()> {
+        let current = self.current_buffer;
+        let mut written = 0;
+        let mut failed = Vec::new();
+        for index in 0..self.number_of_buffers() {
+            self.switch_buffer(index);
+            if self.dirty == 0 {
+                continue;
+            }
+            match self.save_file() {
+                Ok(()) if self.dirty == 0 => written += 1,
+                _ => failed.push(
+                    self.editor_rows
+                        .filename
+                        .as_ref()
+                        .and_then(|path| path.file_name())
+                        .and_then(|name| name.to_str())
+                        .unwrap_or("[No Name]")
+                        .to_string(),
+                ),
+            }
+        }
+        self.switch_buffer(current);
+        self.status_message.set_message(if failed.is_empty() {
+            format!("{} buffers written to disk", written)
+        } else {
+            format!(
+                "{} buffers written to disk, failed to save {}",
+                written,
+                failed.join(", ")
+            )
+        });
+        Ok(())
+    }
+
     pub fn clear_screen() -> crossterm::Result<()> {
         execute!(stdout(), terminal::Clear(ClearType::All))?;
         execute!(stdout(), cursor::MoveTo(0, 0))
@@ -255,41 +1041,74 @@ impl Output {
                     }
                     _ => {}
                 }
-                for i in 0..output.editor_rows.number_of_rows() {
+                let mut wrapped = false;
+                let mut found = false;
+                let (origin_x, origin_y) = output.search_index.origin;
+                let rows = output.editor_rows.number_of_rows();
+                if rows == 0 {
+                    return;
+                }
+                // The origin row is visited twice, last for matches before the origin.
+                for i in 0..=rows {
                     let row_index = match output.search_index.y_direction.as_ref() {
                         None => {
                             if output.search_index.x_direction.is_none() {
-                                output.search_index.y_index = i;
+                                wrapped = origin_y + i >= rows;
+                                output.search_index.y_index = (origin_y + i) % rows;
                             }
                             output.search_index.y_index
                         }
                         Some(dir) => {
+                            // Past the last row the search continues from the other end.
+                            let y_index = output.search_index.y_index;
                             if matches!(dir, SearchDirection::Forward) {
-                                output.search_index.y_index + i + 1
+                                wrapped = y_index + i + 1 >= rows;
+                                (y_index + i + 1) % rows
                             } else {
-                                let res = output.search_index.y_index.saturating_sub(i);
-                                if res == 0 {
-                                    break;
-                                }
-                                res - 1
+                                wrapped = i + 1 > y_index;
+                                (y_index + rows - i - 1) % rows
                             }
                         }
                     };
-                    if row_index > output.editor_rows.number_of_rows() - 1 {
+                    if row_index > rows - 1 {
                         break;
                     }
                     let row = output.editor_rows.get_editor_row_mut(row_index);
+                    // Matches must lie within `lo..hi` of the row's render.
+                    let (lo, hi) = match output.search_index.bounds {
+                        Some(((start_x, start_y), (end_x, end_y))) => {
+                            if row_index < start_y || row_index > end_y {
+                                continue;
+                            }
+                            (
+                                if row_index == start_y { start_x } else { 0 },
+                                if row_index == end_y {
+                                    end_x
+                                } else {
+                                    row.render.len()
+                                },
+                            )
+                        }
+                        None => (0, row.render.len()),
+                    };
+                    let hi = cmp::min(hi, row.render.len());
+                    let lo = cmp::min(lo, hi);
+                    let find_from = |start: usize| {
+                        let start = start.clamp(lo, hi);
+                        row.render[start..hi]
+                            .find(keyword)
+                            .map(|index| index + start)
+                    };
                     let index = match output.search_index.x_direction.as_ref() {
-                        None => row.render.find(&keyword),
+                        None if output.search_index.y_direction.is_some() => find_from(0),
+                        None if i == 0 => find_from(origin_x),
+                        None => find_from(0).filter(|index| i < rows || *index < origin_x),
                         Some(dir) => {
                             let index = if matches!(dir, SearchDirection::Forward) {
-                                let start =
-                                    cmp::min(row.render.len(), output.search_index.x_index + 1);
-                                row.render[start..]
-                                    .find(&keyword)
-                                    .map(|index| index + start)
+                                find_from(output.search_index.x_index + 1)
                             } else {
-                                row.render[..output.search_index.x_index].rfind(&keyword)
+                                let end = output.search_index.x_index.clamp(lo, hi);
+                                row.render[lo..end].rfind(&keyword).map(|index| index + lo)
                             };
                             if index.is_none() {
                                 break;
@@ -300,27 +1119,63 @@ impl Output {
                     if let Some(index) = index {
                         output.search_index.previous_highlight =
                             Some((row_index, row.highlight.clone()));
-                        (index..index + keyword.len())
-                            .for_each(|index| row.highlight[index] = HighlightType::SearchMatch);
+                        row.highlight
+                            .iter_mut()
+                            .skip(index)
+                            .take(keyword.len())
+                            .for_each(|highlight| *highlight = HighlightType::SearchMatch);
                         output.cursor_controller.cursor_y = row_index;
                         output.search_index.y_index = row_index;
                         output.search_index.x_index = index;
                         output.cursor_controller.cursor_x = row.get_row_content_x(index);
+                        if let Some(start) = output.editor_rows.fold_containing(row_index) {
+                            output.editor_rows.folds.remove(&start);
+                        }
                         output.cursor_controller.row_offset = output.editor_rows.number_of_rows();
+                        output.search_index.wrapped = wrapped;
+                        if wrapped {
+                            output.prompt_note = Some("Search wrapped".into());
+                        }
+                        found = true;
                         break;
                     }
                 }
+                if !found && !keyword.is_empty() {
+                    output.prompt_note = Some("No match".into());
+                    output.ring_bell();
+                }
             }
         }
     }
 
     pub fn find(&mut self) -> io::Result<()> {
+        self.editor_rows.load_all(self.syntax_highlight.as_deref());
         let cursor_controller = self.cursor_controller;
+        let render_position = |(x, y): (usize, usize)| {
+            let render_x = self
+                .editor_rows
+                .row_contents
+                .get(y)
+                .map_or(0, |row| row.get_render_x(x));
+            (render_x, y)
+        };
+        let bounds = self
+            .cursor_controller
+            .selection()
+            .map(|(start, end)| (render_position(start), render_position(end)));
+        self.search_index.origin = match bounds {
+            Some((start, _)) => start,
+            None => render_position((cursor_controller.cursor_x, cursor_controller.cursor_y)),
+        };
+        self.search_index.bounds = bounds;
+        self.cursor_controller.clear_selection();
+        let message = if bounds.is_some() {
+            "Search in selection: {} (Use ESC / Arrows / Enter)"
+        } else {
+            "Search: {} (Use ESC / Arrows / Enter)"
+        };
         if self
-            .prompt_callback(
-                "Search: {} (Use ESC / Arrows / Enter)",
-                Some(&Output::find_callback),
-            )
+            .prompt_with(message, Some(&Output::find_callback), PromptKind::Search)
             .is_none()
         {
             self.cursor_controller = cursor_controller
@@ -328,161 +1183,1891 @@ impl Output {
         Ok(())
     }
 
-    pub fn draw_message_bar(&mut self) {
-        queue!(
-            self.editor_contents,
-            terminal::Clear(ClearType::UntilNewLine)
-        )
-        .unwrap();
-        if let Some(msg) = self.status_message.message() {
-            self.editor_contents
-                .push_str(&msg[..cmp::min(self.win_size.0, msg.len())]);
-        }
-    }
-
-    pub fn delete_char(&mut self) {
+    /// Replaces every occurrence of a prompted string within the selection, or in the
+    /// whole buffer when nothing is selected.
+    pub fn replace(&mut self) {
         if self.editor_rows.edit_mode == EditMode::READONLY {
-            self.status_message
-                .set_message("Failed to edit readonly buffer".into());
+            self.alert("Failed to edit readonly buffer".into());
             return;
         }
-
-        if self.cursor_controller.cursor_y == self.editor_rows.number_of_rows() {
+        self.editor_rows.load_all(self.syntax_highlight.as_deref());
+        let rows = self.editor_rows.number_of_rows();
+        if rows == 0 {
             return;
         }
-        if self.cursor_controller.cursor_y == 0 && self.cursor_controller.cursor_x == 0 {
+        let selection = self.cursor_controller.selection();
+        let ((start_x, start_y), (end_x, end_y)) = match selection {
+            Some((start, (_, end_y))) if end_y >= rows => (start, (usize::MAX, rows - 1)),
+            Some(selection) => selection,
+            None => ((0, 0), (usize::MAX, rows - 1)),
+        };
+        let message = if selection.is_some() {
+            "Replace in selection: {} (ESC to cancel)"
+        } else {
+            "Replace: {} (ESC to cancel)"
+        };
+        let query = match self.prompt(message) {
+            Some(query) => query,
+            None => return,
+        };
+        let replacement = match self.prompt("Replace with: {} (ESC to cancel)") {
+            Some(replacement) => replacement,
+            None => return,
+        };
+        let mut count = 0;
+        let lines = (start_y..=end_y)
+            .map(|y| {
+                let row = self.editor_rows.get_editor_row(y);
+                let content = &row.row_content;
+                let from = if y == start_y {
+                    row.byte_index(start_x)
+                } else {
+                    0
+                };
+                let to = if y == end_y {
+                    row.byte_index(end_x)
+                } else {
+                    content.len()
+                };
+                count += content[from..to].matches(&query).count();
+                format!(
+                    "{}{}{}",
+                    &content[..from],
+                    content[from..to].replace(&query, &replacement),
+                    &content[to..]
+                )
+            })
+            .collect();
+        if count == 0 {
+            self.alert(format!("No matches for {}", query));
             return;
         }
-        if self.cursor_controller.cursor_x > 0 {
-            self.editor_rows
+        self.replace_rows(start_y, end_y, lines);
+        self.status_message
+            .set_message(format!("Replaced {} occurrences", count));
+    }
+
+    /// Moves the overlay highlight for arrow keys, returning `false` for any other key.
+    fn move_overlay_selection(&mut self, key_code: KeyCode) -> bool {
+        match key_code {
+            KeyCode::Up => self.overlay_selected = self.overlay_selected.saturating_sub(1),
+            KeyCode::Down => {
+                self.overlay_selected = cmp::min(
+                    self.overlay_selected + 1,
+                    self.overlay.len().saturating_sub(1),
+                )
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn pick_matches(query: &str, candidates: &[String]) -> Vec<String> {
+        let mut matches = candidates
+            .iter()
+            .filter_map(|candidate| fuzzy::score(query, candidate).map(|score| (score, candidate)))
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|(score, _)| *score);
+        matches
+            .into_iter()
+            .take(PICKER_RESULTS)
+            .map(|(_, candidate)| candidate.clone())
+            .collect()
+    }
+
+    /// Prompts with `candidates` listed in an overlay, filtered by a fuzzy query,
+    /// and returns the one picked, or `None` if cancelled or nothing matched.
+    pub fn pick(&mut self, message: &str, candidates: &[String]) -> Option<String> {
+        let callback = |output: &mut Output, query: &str, key_code: KeyCode| {
+            if matches!(key_code, KeyCode::Enter | KeyCode::Esc)
+                || output.move_overlay_selection(key_code)
+            {
+                return;
+            }
+            output.overlay = Output::pick_matches(query, candidates);
+            output.overlay_selected = 0;
+        };
+        self.overlay = Output::pick_matches("", candidates);
+        self.overlay_selected = 0;
+        let query = self.prompt_callback(message, Some(&callback));
+        let selected = self.overlay.get(self.overlay_selected).cloned();
+        self.overlay.clear();
+        if query.is_some() && selected.is_none() {
+            self.alert("No match".into());
+        }
+        query.and(selected)
+    }
+
+    /// Lists files under the working directory in a picker and opens the one chosen.
+    pub fn quick_open(&mut self) -> crossterm::Result<()> {
+        let root = env::current_dir()?;
+        let files = fuzzy::files_under(&root, self.show_ignored);
+        if let Some(file) = self.pick("Open: {} (Use ESC / Arrows / Enter)", &files) {
+            self.open_file(root.join(file))?
+        }
+        Ok(())
+    }
+
+    pub fn goto_line(&mut self) {
+        let line = match self.prompt("Go to line: {} (ESC to cancel)") {
+            Some(line) => line,
+            None => return,
+        };
+        match line.trim().parse::<usize>() {
+            Ok(line) if line > 0 => self.jump_to(line, 1),
+            _ => self
+                .status_message
+                .set_message(format!("Invalid line number: {}", line)),
+        }
+    }
+
+    /// Moves the cursor to a prompted percentage of the way through the buffer.
+    pub fn goto_percent(&mut self) {
+        let percent = match self.prompt("Go to percent: {} (0-100, ESC to cancel)") {
+            Some(percent) => percent,
+            None => return,
+        };
+        let percent = match percent.trim().trim_end_matches('%').parse::<i64>() {
+            Ok(percent) => percent.clamp(0, 100) as usize,
+            Err(_) => {
+                self.status_message
+                    .set_message(format!("Invalid percentage: {}", percent));
+                return;
+            }
+        };
+        self.editor_rows.load_all(self.syntax_highlight.as_deref());
+        let rows = self.editor_rows.number_of_rows();
+        self.jump_to(percent * rows / 100 + 1, 1);
+    }
+
+    /// Moves the cursor to the 1-based `line` and `column`, clamped to the buffer.
+    pub fn jump_to(&mut self, line: usize, column: usize) {
+        self.editor_rows
+            .load_rows(line, self.syntax_highlight.as_deref());
+        self.cursor_controller.clear_selection();
+        let cursor_y = cmp::min(line, self.editor_rows.number_of_rows()).saturating_sub(1);
+        self.cursor_controller.cursor_y = cursor_y;
+        self.cursor_controller.cursor_x = match self.editor_rows.row_contents.get(cursor_y) {
+            Some(row) => cmp::min(column.saturating_sub(1), row.char_count()),
+            None => 0,
+        };
+    }
+
+    pub fn toggle_read_only(&mut self) {
+        if self.editor_rows.file_type == FileType::DIR {
+            return;
+        }
+        self.editor_rows.edit_mode = match self.editor_rows.edit_mode {
+            EditMode::NORMAL => EditMode::READONLY,
+            EditMode::READONLY => EditMode::NORMAL,
+        };
+    }
+
+    pub fn draw_message_bar(&mut self) {
+        queue!(
+            self.editor_contents,
+            terminal::Clear(ClearType::UntilNewLine)
+        )
+        .unwrap();
+        if let Some(msg) = self.status_message.message() {
+            self.editor_contents
+                .push_str(&msg[..cmp::min(self.win_size.0, msg.len())]);
+        }
+    }
+
+    pub fn delete_char(&mut self) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        self.cursor_controller.clear_selection();
+
+        if self.cursor_controller.cursor_y == self.editor_rows.number_of_rows() {
+            return;
+        }
+        if self.cursor_controller.cursor_y == 0 && self.cursor_controller.cursor_x == 0 {
+            return;
+        }
+        if self.cursor_controller.cursor_x > 0 {
+            self.editor_rows
                 .get_editor_row_mut(self.cursor_controller.cursor_y)
                 .delete_char(self.cursor_controller.cursor_x - 1);
             self.cursor_controller.cursor_x -= 1;
         } else {
-            let previous_row_content = self
+            self.cursor_controller.cursor_x = self
                 .editor_rows
-                .get_row(self.cursor_controller.cursor_y - 1);
-            self.cursor_controller.cursor_x = previous_row_content.len();
+                .get_editor_row(self.cursor_controller.cursor_y - 1)
+                .char_count();
             self.editor_rows
                 .join_adjacent_rows(self.cursor_controller.cursor_y);
+            self.rows_changed(self.cursor_controller.cursor_y, 1, 0);
             self.cursor_controller.cursor_y -= 1;
         }
-        if let Some(it) = self.syntax_highlight.as_ref() {
-            it.update_syntax(
-                self.cursor_controller.cursor_y,
-                &mut self.editor_rows.row_contents,
-            );
+        if let Some(it) = self.syntax_highlight.as_ref() {
+            it.update_syntax(
+                self.cursor_controller.cursor_y,
+                &mut self.editor_rows.row_contents,
+            );
+        }
+        self.mark_dirty();
+    }
+
+    pub fn insert_newline(&mut self) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        self.cursor_controller.clear_selection();
+        if self.split_bracket_pair() || self.continue_comment() {
+            return;
+        }
+
+        if self.cursor_controller.cursor_x == 0 {
+            self.editor_rows
+                .insert_row(self.cursor_controller.cursor_y, String::new());
+            self.rows_changed(self.cursor_controller.cursor_y, 0, 1);
+        } else {
+            let current_row = self
+                .editor_rows
+                .get_editor_row_mut(self.cursor_controller.cursor_y);
+            let split_at = current_row.byte_index(self.cursor_controller.cursor_x);
+            let new_row_content = current_row.row_content[split_at..].into();
+            current_row.row_content.truncate(split_at);
+            EditorRows::render_row(current_row);
+            self.editor_rows
+                .insert_row(self.cursor_controller.cursor_y + 1, new_row_content);
+            self.rows_changed(self.cursor_controller.cursor_y + 1, 0, 1);
+            if let Some(it) = self.syntax_highlight.as_ref() {
+                it.update_syntax(
+                    self.cursor_controller.cursor_y,
+                    &mut self.editor_rows.row_contents,
+                );
+                it.update_syntax(
+                    self.cursor_controller.cursor_y + 1,
+                    &mut self.editor_rows.row_contents,
+                )
+            }
+        }
+        self.cursor_controller.cursor_x = 0;
+        self.cursor_controller.cursor_y += 1;
+        self.mark_dirty();
+    }
+
+    /// Between a bracket and its closer, as in `{|}`, opens an indented blank row
+    /// between them with the closer on its own row at the original indentation.
+    /// Returns whether the cursor was between such a pair.
+    fn split_bracket_pair(&mut self) -> bool {
+        let (x, y) = (
+            self.cursor_controller.cursor_x,
+            self.cursor_controller.cursor_y,
+        );
+        let row = match self.editor_rows.row_contents.get(y) {
+            Some(row) if x > 0 => row,
+            _ => return false,
+        };
+        let mut around = row.row_content.chars().skip(x - 1);
+        if !matches!(
+            (around.next(), around.next()),
+            (Some('{'), Some('}')) | (Some('['), Some(']')) | (Some('('), Some(')'))
+        ) {
+            return false;
+        }
+        let indent = row
+            .row_content
+            .chars()
+            .take_while(|ch| *ch == ' ' || *ch == '\t')
+            .collect::<String>();
+        let text = format!("\n{}\t\n{}", indent, indent);
+        self.editor_rows
+            .insert_str(y, x, &text, self.syntax_highlight.as_deref());
+        self.rows_changed(y + 1, 0, 2);
+        self.cursor_controller.cursor_x = indent.chars().count() + 1;
+        self.cursor_controller.cursor_y = y + 1;
+        self.mark_dirty();
+        true
+    }
+
+    /// Past the prefix of a row holding a line comment, as in `    // a|b`, moves the
+    /// rest of the row onto a new one that starts with the same indentation, prefix and
+    /// spacing. Returns whether the row was continued that way.
+    fn continue_comment(&mut self) -> bool {
+        if !self.continue_comments {
+            return false;
+        }
+        let (x, y) = (
+            self.cursor_controller.cursor_x,
+            self.cursor_controller.cursor_y,
+        );
+        let (syntax, row) = match (
+            self.syntax_highlight.as_deref(),
+            self.editor_rows.row_contents.get(y),
+        ) {
+            (Some(syntax), Some(row)) => (syntax, row),
+            _ => return false,
+        };
+        let text = row.row_content.trim_start_matches([' ', '\t']);
+        let indent = &row.row_content[..row.row_content.len() - text.len()];
+        let line_comment = |prefix: &str| {
+            syntax
+                .comment_start()
+                .iter()
+                .any(|start| prefix.starts_with(start))
+        };
+        let prefix = match syntax
+            .doc_comments()
+            .iter()
+            .filter(|prefix| line_comment(prefix))
+            .chain(syntax.comment_start())
+            .filter(|prefix| text.starts_with(**prefix))
+            .max_by_key(|prefix| prefix.len())
+        {
+            Some(prefix) => *prefix,
+            None => return false,
+        };
+        if x < indent.len() + prefix.len() {
+            return false;
+        }
+        let comment = &text[prefix.len()..];
+        let spacing = &comment[..comment.len() - comment.trim_start_matches([' ', '\t']).len()];
+        let continuation = format!("{}{}{}", indent, prefix, spacing);
+        self.editor_rows.insert_str(
+            y,
+            x,
+            &format!("\n{}", continuation),
+            self.syntax_highlight.as_deref(),
+        );
+        self.rows_changed(y + 1, 0, 1);
+        self.cursor_controller.cursor_x = continuation.chars().count();
+        self.cursor_controller.cursor_y = y + 1;
+        self.mark_dirty();
+        true
+    }
+
+    pub fn delete_to_line_edge(&mut self, direction: SearchDirection) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        self.cursor_controller.clear_selection();
+        let (x, y) = (
+            self.cursor_controller.cursor_x,
+            self.cursor_controller.cursor_y,
+        );
+        let len = match self.editor_rows.row_contents.get(y) {
+            Some(row) => row.char_count(),
+            None => return,
+        };
+        let (start, end) = match direction {
+            SearchDirection::Forward => (x, len),
+            SearchDirection::Backward => (0, x),
+        };
+        if start == end {
+            return;
+        }
+        self.editor_rows
+            .delete_range((start, y), (end, y), self.syntax_highlight.as_deref());
+        self.cursor_controller.cursor_x = start;
+        self.mark_dirty();
+    }
+
+    pub fn delete_word(&mut self, direction: SearchDirection) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        self.cursor_controller.clear_selection();
+        let cursor = (
+            self.cursor_controller.cursor_x,
+            self.cursor_controller.cursor_y,
+        );
+        let boundary = self
+            .cursor_controller
+            .word_boundary(direction, &self.editor_rows);
+        if boundary == cursor {
+            return;
+        }
+        let (start, end) = match direction {
+            SearchDirection::Forward => (cursor, boundary),
+            SearchDirection::Backward => (boundary, cursor),
+        };
+        self.editor_rows
+            .delete_range(start, end, self.syntax_highlight.as_deref());
+        if start.1 != end.1 {
+            self.rows_changed(start.1 + 1, 1, 0);
+        }
+        self.cursor_controller.cursor_x = start.0;
+        self.cursor_controller.cursor_y = start.1;
+        self.mark_dirty();
+    }
+
+    pub fn move_word(&mut self, direction: SearchDirection) {
+        self.cursor_controller.clear_selection();
+        let (x, y) = self
+            .cursor_controller
+            .word_boundary(direction, &self.editor_rows);
+        self.cursor_controller.cursor_x = x;
+        self.cursor_controller.cursor_y = y;
+    }
+
+    pub fn insert_char(&mut self, ch: char) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        self.cursor_controller.clear_selection();
+
+        if self.cursor_controller.cursor_y == self.editor_rows.number_of_rows() {
+            self.editor_rows
+                .insert_row(self.editor_rows.number_of_rows(), String::new());
+            self.mark_dirty();
+        }
+        let row = self
+            .editor_rows
+            .get_editor_row_mut(self.cursor_controller.cursor_y);
+        if self.overwrite && self.cursor_controller.cursor_x < row.char_count() {
+            row.replace_char(self.cursor_controller.cursor_x, ch);
+        } else {
+            row.insert_char(self.cursor_controller.cursor_x, ch);
+        }
+        if let Some(it) = self.syntax_highlight.as_ref() {
+            it.update_syntax(
+                self.cursor_controller.cursor_y,
+                &mut self.editor_rows.row_contents,
+            )
+        }
+        self.cursor_controller.cursor_x += 1;
+        self.mark_dirty();
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        let ((start_x, start_y), (end_x, end_y)) = self.cursor_controller.selection()?;
+        let start_row = self.editor_rows.get_editor_row(start_y);
+        let end_row = self.editor_rows.get_editor_row(end_y);
+        let (start, end) = (start_row.byte_index(start_x), end_row.byte_index(end_x));
+        if start_y == end_y {
+            return Some(start_row.row_content[start..end].into());
+        }
+        let mut text = String::from(&start_row.row_content[start..]);
+        for i in start_y + 1..end_y {
+            text.push('\n');
+            text.push_str(self.editor_rows.get_row(i));
+        }
+        text.push('\n');
+        text.push_str(&end_row.row_content[..end]);
+        Some(text)
+    }
+
+    pub fn copy_selection(&mut self) {
+        match self.selected_text() {
+            Some(text) => {
+                self.status_message
+                    .set_message(format!("{} characters copied", text.chars().count()));
+                self.clipboard = Some(text);
+            }
+            None => self.status_message.set_message("Nothing selected".into()),
+        }
+    }
+
+    /// Pipes the selection, or the whole buffer when nothing is selected, to the
+    /// configured copy command, for terminals where that's how the system clipboard
+    /// is reached.
+    pub fn copy_to_command(&mut self) {
+        let text = match self.selected_text() {
+            Some(text) => text,
+            None => {
+                self.editor_rows.load_all(self.syntax_highlight.as_deref());
+                self.editor_rows
+                    .row_contents
+                    .iter()
+                    .map(|row| row.row_content.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+        let spawned = Command::new("sh")
+            .arg("-c")
+            .arg(&self.copy_command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(err) => {
+                self.alert(format!("Cannot run {}: {}", self.copy_command, err));
+                return;
+            }
+        };
+        // Dropping stdin closes it, so the command sees the end of the text.
+        let written = child
+            .stdin
+            .take()
+            .map_or(Ok(()), |mut stdin| stdin.write_all(text.as_bytes()));
+        match (written, child.wait()) {
+            (_, Err(err)) => self.alert(format!("{}: {}", self.copy_command, err)),
+            (_, Ok(status)) if !status.success() => {
+                self.alert(format!("{} failed: {}", self.copy_command, status))
+            }
+            (Err(err), Ok(_)) => self.alert(format!(
+                "{} did not take all of the text: {}",
+                self.copy_command, err
+            )),
+            (Ok(()), Ok(_)) => self.status_message.set_message(format!(
+                "{} characters sent to {}",
+                text.chars().count(),
+                self.copy_command
+            )),
+        }
+    }
+
+    /// Prompts for a shell command and replaces the selection, or the whole buffer
+    /// when nothing is selected, with what it prints for that text. If the command
+    /// fails, its error output is shown and the buffer is left as it was.
+    pub fn filter_through_command(&mut self) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        match self.prompt("Filter through: {} (ESC to cancel)") {
+            Some(command) if !command.trim().is_empty() => self.filter_through(&command),
+            _ => self.status_message.set_message("Filter aborted".into()),
+        }
+    }
+
+    /// Replaces the selection, or the whole buffer, with the output of `command`.
+    fn filter_through(&mut self, command: &str) {
+        let whole_buffer = self.cursor_controller.selection().is_none();
+        if whole_buffer {
+            self.editor_rows.load_all(self.syntax_highlight.as_deref());
+            if self.editor_rows.number_of_rows() == 0 {
+                self.editor_rows.insert_row(0, String::new());
+            }
+            let last = self.editor_rows.number_of_rows() - 1;
+            self.cursor_controller.selection_anchor = Some((0, 0));
+            self.cursor_controller.cursor_y = last;
+            self.cursor_controller.cursor_x = self.editor_rows.get_editor_row(last).char_count();
+        }
+        let text = self.selected_text().unwrap_or_default();
+        match Self::run_filter(command, &text) {
+            Ok(output) => {
+                self.delete_selection();
+                self.insert_text(&output);
+                self.status_message
+                    .set_message(format!("Filtered through {}", command));
+            }
+            Err(err) => {
+                if whole_buffer {
+                    self.cursor_controller.clear_selection();
+                }
+                self.alert(err);
+            }
+        }
+    }
+
+    /// Runs `command` with `input` on its standard input, returning its output with
+    /// the final line ending removed when `input` didn't have one.
+    fn run_filter(command: &str, input: &str) -> Result<String, String> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Cannot run {}: {}", command, err))?;
+        let terminated = input.is_empty() || input.ends_with('\n');
+        let mut input = input.to_string();
+        if !terminated {
+            input.push('\n');
+        }
+        // Writing from another thread keeps a command that answers before reading all
+        // of its input from blocking on a full pipe.
+        let writer = child
+            .stdin
+            .take()
+            .map(|mut stdin| thread::spawn(move || stdin.write_all(input.as_bytes())));
+        let output = child
+            .wait_with_output()
+            .map_err(|err| format!("{}: {}", command, err))?;
+        // A command like `head` may stop reading early, which only matters if it failed.
+        let written = writer.map_or(Ok(()), |writer| {
+            writer
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("writer panicked")))
+        });
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = stderr
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(if stderr.is_empty() {
+                format!("{} failed: {}", command, output.status)
+            } else {
+                format!("{}: {}", command, stderr)
+            });
+        }
+        match written {
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(format!("Cannot write to {}: {}", command, err))
+            }
+            _ => {}
+        }
+        let mut text = String::from_utf8(output.stdout)
+            .map_err(|_| format!("{}: output is not valid UTF-8", command))?;
+        if !terminated && text.ends_with('\n') {
+            text.pop();
+            if text.ends_with('\r') {
+                text.pop();
+            }
+        }
+        Ok(text)
+    }
+
+    pub fn delete_selection(&mut self) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        let ((start_x, start_y), (end_x, end_y)) = match self.cursor_controller.selection() {
+            Some(selection) => selection,
+            None => return,
+        };
+        self.editor_rows.delete_range(
+            (start_x, start_y),
+            (end_x, end_y),
+            self.syntax_highlight.as_deref(),
+        );
+        self.rows_changed(start_y + 1, end_y - start_y, 0);
+        self.cursor_controller.clear_selection();
+        self.cursor_controller.cursor_x = start_x;
+        self.cursor_controller.cursor_y = start_y;
+        self.mark_dirty();
+    }
+
+    pub fn paste(&mut self) {
+        if let Some(text) = self.clipboard.clone() {
+            if self.cursor_controller.selection().is_some() {
+                self.delete_selection();
+            }
+            self.insert_text(&text);
+        }
+    }
+
+    /// Inserts `text`, which may span several lines, at the cursor and moves past it.
+    pub fn insert_text(&mut self, text: &str) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        self.cursor_controller.clear_selection();
+        let at = self.cursor_controller.cursor_y;
+        let (end_x, end_y) = self.editor_rows.insert_str(
+            at,
+            self.cursor_controller.cursor_x,
+            text,
+            self.syntax_highlight.as_deref(),
+        );
+        self.rows_changed(at + 1, 0, end_y - at);
+        self.cursor_controller.cursor_x = end_x;
+        self.cursor_controller.cursor_y = end_y;
+        self.mark_dirty();
+    }
+
+    /// Returns the rows covered by the selection, or just the cursor row without one.
+    fn selected_rows(&self) -> (usize, usize) {
+        match self.cursor_controller.selection() {
+            Some(((_, start_y), (end_x, end_y))) if end_x == 0 && end_y > start_y => {
+                (start_y, end_y - 1)
+            }
+            Some(((_, start_y), (_, end_y))) => (start_y, end_y),
+            None => (
+                self.cursor_controller.cursor_y,
+                self.cursor_controller.cursor_y,
+            ),
+        }
+    }
+
+    pub fn indent_lines(&mut self, unindent: bool) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        if self.cursor_controller.cursor_y >= self.editor_rows.number_of_rows() {
+            return;
+        }
+        let (start_y, end_y) = self.selected_rows();
+        let mut changed = false;
+        for at in start_y..=end_y {
+            let row = self.editor_rows.get_editor_row_mut(at);
+            let (added, removed) = if unindent {
+                let count = if row.row_content.starts_with('\t') {
+                    1
+                } else {
+                    row.row_content
+                        .chars()
+                        .take(TAB_STOP)
+                        .take_while(|c| *c == ' ')
+                        .count()
+                };
+                row.row_content.drain(..count);
+                (0, count)
+            } else {
+                row.row_content.insert(0, '\t');
+                (1, 0)
+            };
+            if added == removed {
+                continue;
+            }
+            changed = true;
+            EditorRows::render_row(row);
+            let shift_x = |x: usize| (x + added).saturating_sub(removed);
+            if self.cursor_controller.cursor_y == at {
+                self.cursor_controller.cursor_x = shift_x(self.cursor_controller.cursor_x);
+            }
+            if let Some((x, y)) = self.cursor_controller.selection_anchor.as_mut() {
+                if *y == at {
+                    *x = shift_x(*x);
+                }
+            }
+            if let Some(it) = self.syntax_highlight.as_ref() {
+                it.update_syntax(at, &mut self.editor_rows.row_contents);
+            }
+        }
+        if changed {
+            self.mark_dirty();
+        }
+    }
+
+    /// Returns the rows covered by the selection, or the whole buffer without one.
+    fn selected_or_all_rows(&self) -> Option<(usize, usize)> {
+        if self.cursor_controller.selection().is_some() {
+            Some(self.selected_rows())
+        } else {
+            self.editor_rows
+                .number_of_rows()
+                .checked_sub(1)
+                .map(|last| (0, last))
+        }
+    }
+
+    /// Replaces rows `start_y..=end_y` with `lines`, re-rendering and re-highlighting
+    /// them and keeping the cursor inside the buffer.
+    fn replace_rows(&mut self, start_y: usize, end_y: usize, lines: Vec<String>) {
+        let count = lines.len();
+        self.rows_changed(start_y, end_y - start_y + 1, count);
+        self.editor_rows.row_contents.splice(
+            start_y..=end_y,
+            lines.into_iter().map(|line| {
+                let mut row = Row::new(line, String::new());
+                EditorRows::render_row(&mut row);
+                row
+            }),
+        );
+        if let Some(it) = self.syntax_highlight.as_ref() {
+            for at in start_y..start_y + count {
+                it.update_syntax(at, &mut self.editor_rows.row_contents);
+            }
+        }
+        self.cursor_controller.clear_selection();
+        self.cursor_controller.cursor_y = cmp::min(
+            self.cursor_controller.cursor_y,
+            self.editor_rows.number_of_rows().saturating_sub(1),
+        );
+        self.cursor_controller.cursor_x = cmp::min(
+            self.cursor_controller.cursor_x,
+            self.editor_rows
+                .row_contents
+                .get(self.cursor_controller.cursor_y)
+                .map_or(0, Row::char_count),
+        );
+        self.mark_dirty();
+    }
+
+    /// Adds `amount` to the number under the cursor, or the next one after it on the
+    /// row, keeping its sign and any leading zeros.
+    pub fn increment_number(&mut self, amount: i64) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        let at = self.cursor_controller.cursor_y;
+        if at >= self.editor_rows.number_of_rows() {
+            return;
+        }
+        let row = self.editor_rows.get_editor_row(at);
+        let bytes = row.row_content.as_bytes();
+        let cursor = row.byte_index(self.cursor_controller.cursor_x);
+        let mut start = match (cursor..bytes.len()).find(|i| bytes[*i].is_ascii_digit()) {
+            Some(start) => start,
+            None => {
+                self.status_message
+                    .set_message("No number under cursor".into());
+                return;
+            }
+        };
+        while start > 0 && bytes[start - 1].is_ascii_digit() {
+            start -= 1;
+        }
+        let mut end = start;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+        let digits = end - start;
+        let zero_padded = digits > 1 && bytes[start] == b'0';
+        if start > 0 && bytes[start - 1] == b'-' {
+            start -= 1;
+        }
+        let value = match row.row_content[start..end].parse::<i128>() {
+            Ok(value) => value + amount as i128,
+            Err(_) => {
+                self.status_message.set_message("Number too large".into());
+                return;
+            }
+        };
+        let width = if zero_padded { digits } else { 0 };
+        let number = format!(
+            "{}{:0width$}",
+            if value < 0 { "-" } else { "" },
+            value.unsigned_abs(),
+            width = width
+        );
+        let row = self.editor_rows.get_editor_row_mut(at);
+        row.row_content.replace_range(start..end, &number);
+        EditorRows::render_row(row);
+        self.cursor_controller.cursor_x =
+            row.row_content[..start + number.len() - 1].chars().count();
+        if let Some(it) = self.syntax_highlight.as_ref() {
+            it.update_syntax(at, &mut self.editor_rows.row_contents);
+        }
+        self.cursor_controller.clear_selection();
+        self.mark_dirty();
+    }
+
+    /// Types the current time, formatted with `timestamp_format`, at the cursor.
+    pub fn insert_timestamp(&mut self) {
+        self.insert_text(&timestamp::now(&self.timestamp_format));
+    }
+
+    /// Re-splits the paragraph around the cursor (its run of non-blank rows) on word
+    /// boundaries so no row is longer than `wrap_column`, keeping the first row's indent.
+    pub fn reflow_paragraph(&mut self) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        let is_blank = |at: usize| self.editor_rows.get_row(at).trim().is_empty();
+        let at = self.cursor_controller.cursor_y;
+        if at >= self.editor_rows.number_of_rows() || is_blank(at) {
+            return;
+        }
+        let mut start_y = at;
+        while start_y > 0 && !is_blank(start_y - 1) {
+            start_y -= 1;
+        }
+        let mut end_y = at;
+        while end_y + 1 < self.editor_rows.number_of_rows() && !is_blank(end_y + 1) {
+            end_y += 1;
+        }
+        let first_row = self.editor_rows.get_row(start_y);
+        let indent = &first_row[..first_row.len() - first_row.trim_start().len()];
+        // Rows are measured as rendered, with tabs in the indent expanded.
+        let indent_width = indent.chars().fold(0, |width, ch| {
+            if ch == '\t' {
+                width + TAB_STOP - width % TAB_STOP
+            } else {
+                width + 1
+            }
+        });
+        let mut lines = Vec::new();
+        let mut line = String::from(indent);
+        let mut width = indent_width;
+        for word in (start_y..=end_y).flat_map(|y| self.editor_rows.get_row(y).split_whitespace()) {
+            let word_width = word.chars().count();
+            if width > indent_width && width + 1 + word_width > self.wrap_column {
+                lines.push(mem::replace(&mut line, String::from(indent)));
+                width = indent_width;
+            }
+            if width > indent_width {
+                line.push(' ');
+                width += 1;
+            }
+            line.push_str(word);
+            width += word_width;
+        }
+        lines.push(line);
+        let count = lines.len();
+        self.replace_rows(start_y, end_y, lines);
+        self.cursor_controller.cursor_y = start_y + count - 1;
+        self.cursor_controller.cursor_x = self
+            .editor_rows
+            .get_editor_row(start_y + count - 1)
+            .char_count();
+    }
+
+    pub fn sort_lines(&mut self, descending: bool, ignore_case: bool) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        let (start_y, end_y) = match self.selected_or_all_rows() {
+            Some(rows) => rows,
+            None => return,
+        };
+        let mut lines = self.editor_rows.row_contents[start_y..=end_y]
+            .iter()
+            .map(|row| row.row_content.clone())
+            .collect::<Vec<String>>();
+        if ignore_case {
+            lines.sort_by_cached_key(|line| line.to_lowercase());
+        } else {
+            lines.sort();
+        }
+        if descending {
+            lines.reverse();
+        }
+        self.replace_rows(start_y, end_y, lines);
+        self.status_message
+            .set_message(format!("Sorted {} lines", end_y - start_y + 1));
+    }
+
+    /// Appends the next row to the cursor row, separated by a single space when
+    /// both are non-empty, and leaves the cursor at the join point.
+    pub fn join_lines(&mut self) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        let at = self.cursor_controller.cursor_y;
+        if at + 1 >= self.editor_rows.number_of_rows() {
+            return;
+        }
+        self.cursor_controller.clear_selection();
+        let next_row = self.editor_rows.get_editor_row_mut(at + 1);
+        let trimmed = next_row.row_content.trim_start().len();
+        next_row
+            .row_content
+            .drain(..next_row.row_content.len() - trimmed);
+        let needs_space = trimmed > 0;
+        let current_row = self.editor_rows.get_editor_row_mut(at);
+        let join_x = current_row.char_count();
+        if needs_space && !current_row.row_content.is_empty() {
+            current_row.row_content.push(' ');
+        }
+        self.editor_rows.join_adjacent_rows(at + 1);
+        self.rows_changed(at + 1, 1, 0);
+        if let Some(it) = self.syntax_highlight.as_ref() {
+            it.update_syntax(at, &mut self.editor_rows.row_contents);
+        }
+        self.cursor_controller.cursor_x = join_x;
+        self.mark_dirty();
+    }
+
+    /// Returns the character range of the word around the cursor in the cursor row.
+    fn word_at_cursor(&self) -> Option<(usize, usize)> {
+        self.token_at_cursor(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    /// Returns the character range of the run of `is_token` characters around the
+    /// cursor in the cursor row.
+    fn token_at_cursor(&self, is_token: impl Fn(char) -> bool) -> Option<(usize, usize)> {
+        let row = self
+            .editor_rows
+            .row_contents
+            .get(self.cursor_controller.cursor_y)?;
+        let chars = row.row_content.chars().collect::<Vec<_>>();
+        let cursor_x = cmp::min(self.cursor_controller.cursor_x, chars.len());
+        let start = cursor_x
+            - chars[..cursor_x]
+                .iter()
+                .rev()
+                .take_while(|c| is_token(**c))
+                .count();
+        let end = cursor_x
+            + chars[cursor_x..]
+                .iter()
+                .take_while(|c| is_token(**c))
+                .count();
+        if start == end {
+            None
+        } else {
+            Some((start, end))
+        }
+    }
+
+    /// Converts the selection, or the word under the cursor, to the next case in
+    /// the upper -> lower -> title cycle.
+    pub fn cycle_case(&mut self) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        let ((start_x, start_y), (end_x, end_y)) = match self.cursor_controller.selection() {
+            Some(selection) => selection,
+            None => match self.word_at_cursor() {
+                Some((start, end)) => (
+                    (start, self.cursor_controller.cursor_y),
+                    (end, self.cursor_controller.cursor_y),
+                ),
+                None => return,
+            },
+        };
+        let text_case = TextCase::next_for(&self.selected_text().unwrap_or_else(|| {
+            let row = self.editor_rows.get_editor_row(start_y);
+            row.row_content[row.byte_index(start_x)..row.byte_index(end_x)].into()
+        }));
+        // Case changes can alter the length, as with `ß` -> `SS`, so the end of the
+        // range moves by however many characters the last row gained or lost.
+        let mut end_delta = 0;
+        for at in start_y..=end_y {
+            let row = self.editor_rows.get_editor_row_mut(at);
+            let from = if at == start_y { start_x } else { 0 };
+            let to = if at == end_y { end_x } else { row.char_count() };
+            let (from, to, count) = (row.byte_index(from), row.byte_index(to), to - from);
+            let converted = text_case.apply(&row.row_content[from..to]);
+            if at == end_y {
+                end_delta = converted.chars().count() as isize - count as isize;
+            }
+            row.row_content.replace_range(from..to, &converted);
+            EditorRows::render_row(row);
+            if let Some(it) = self.syntax_highlight.as_ref() {
+                it.update_syntax(at, &mut self.editor_rows.row_contents);
+            }
+        }
+        let shift_end = |(x, y): (usize, usize)| {
+            if (x, y) == (end_x, end_y) {
+                (x.saturating_add_signed(end_delta), y)
+            } else {
+                (x, y)
+            }
+        };
+        let (cursor_x, _) = shift_end((
+            self.cursor_controller.cursor_x,
+            self.cursor_controller.cursor_y,
+        ));
+        self.cursor_controller.cursor_x = cursor_x;
+        self.cursor_controller.selection_anchor =
+            self.cursor_controller.selection_anchor.map(shift_end);
+        self.mark_dirty();
+    }
+
+    pub fn remove_duplicate_lines(&mut self) {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.alert("Failed to edit readonly buffer".into());
+            return;
+        }
+        let (start_y, end_y) = match self.selected_or_all_rows() {
+            Some(rows) => rows,
+            None => return,
+        };
+        let mut lines = self.editor_rows.row_contents[start_y..=end_y]
+            .iter()
+            .map(|row| row.row_content.clone())
+            .collect::<Vec<String>>();
+        lines.dedup();
+        let removed = end_y - start_y + 1 - lines.len();
+        if removed > 0 {
+            self.replace_rows(start_y, end_y, lines);
+        }
+        self.status_message
+            .set_message(format!("Removed {} duplicate lines", removed));
+    }
+
+    pub fn apply_edit(&mut self, edit: EditCommand) {
+        if !matches!(
+            edit,
+            EditCommand::InsertChar(_)
+                | EditCommand::InsertNewline
+                | EditCommand::DeleteBackward
+                | EditCommand::DeleteForward
+        ) {
+            self.extra_carets.clear();
+        }
+        // With several carets, typing replaces each one's selection, so a renamed
+        // word can be typed over at every occurrence at once.
+        let replace_selections = !self.extra_carets.is_empty();
+        let clear_selection = move |output: &mut Self| {
+            if replace_selections && output.cursor_controller.selection().is_some() {
+                output.delete_selection();
+            }
+        };
+        match edit {
+            EditCommand::InsertChar(ch) => self.edit_at_carets(|output| {
+                clear_selection(output);
+                output.insert_char(ch)
+            }),
+            EditCommand::InsertNewline => self.edit_at_carets(|output| {
+                clear_selection(output);
+                output.insert_newline()
+            }),
+            EditCommand::DeleteBackward | EditCommand::DeleteForward => {
+                self.edit_at_carets(|output| {
+                    if output.cursor_controller.selection().is_some() {
+                        output.delete_selection();
+                        return;
+                    }
+                    if matches!(edit, EditCommand::DeleteForward) {
+                        output
+                            .cursor_controller
+                            .move_cursor(KeyCode::Right, &output.editor_rows);
+                    }
+                    output.delete_char()
+                })
+            }
+            EditCommand::JoinLines => self.join_lines(),
+            EditCommand::Paste => self.paste(),
+            EditCommand::Indent => self.indent_lines(false),
+            EditCommand::Unindent => self.indent_lines(true),
+            EditCommand::Increment(amount) => self.increment_number(amount),
+            EditCommand::DeleteToLineEdge(direction) => self.delete_to_line_edge(direction),
+            EditCommand::DeleteWord(direction) => self.delete_word(direction),
+        }
+        self.last_edit = Some(edit);
+    }
+
+    /// Character offset of `(x, y)` from the start of the buffer, counting each row
+    /// break as one character.
+    fn caret_offset(&self, (x, y): (usize, usize)) -> usize {
+        self.editor_rows.row_contents[..y]
+            .iter()
+            .map(|row| row.char_count() + 1)
+            .sum::<usize>()
+            + x
+    }
+
+    /// The `(x, y)` position at a character offset from `caret_offset`.
+    fn caret_position(&self, mut offset: usize) -> (usize, usize) {
+        for (y, row) in self.editor_rows.row_contents.iter().enumerate() {
+            let len = row.char_count();
+            if offset <= len {
+                return (offset, y);
+            }
+            offset -= len + 1;
+        }
+        (0, self.editor_rows.number_of_rows())
+    }
+
+    /// Runs `edit` at the cursor and at every extra caret, each with its own selection,
+    /// the last position first so the text before each caret is still unchanged when
+    /// it is reached.
+    fn edit_at_carets(&mut self, edit: impl Fn(&mut Self)) {
+        let rows = self.editor_rows.number_of_rows();
+        self.extra_carets
+            .retain(|caret| caret.position.1 < rows && caret.anchor.is_none_or(|(_, y)| y < rows));
+        if self.extra_carets.is_empty() || self.editor_rows.edit_mode == EditMode::READONLY {
+            edit(self);
+            return;
+        }
+        let offset = |output: &Self, (x, y): (usize, usize)| {
+            output.caret_offset((
+                cmp::min(x, output.editor_rows.get_editor_row(y).char_count()),
+                y,
+            ))
+        };
+        let main = offset(
+            self,
+            (
+                self.cursor_controller.cursor_x,
+                self.cursor_controller.cursor_y,
+            ),
+        );
+        let main_anchor = self
+            .cursor_controller
+            .selection_anchor
+            .map(|anchor| offset(self, anchor));
+        // Each caret is its offset and that of its selection's other end.
+        let mut carets = self
+            .extra_carets
+            .iter()
+            .map(|caret| {
+                (
+                    offset(self, caret.position),
+                    caret.anchor.map(|anchor| offset(self, anchor)),
+                )
+            })
+            .chain([(main, main_anchor)])
+            .collect::<Vec<_>>();
+        carets.sort_unstable_by_key(|&(at, anchor)| (cmp::min(at, anchor.unwrap_or(at)), at));
+        carets.dedup_by_key(|(at, _)| *at);
+        let main_index = carets.iter().position(|(at, _)| *at == main).unwrap();
+        for i in (0..carets.len()).rev() {
+            let (at, anchor) = carets[i];
+            let (x, y) = self.caret_position(at);
+            self.cursor_controller.cursor_x = x;
+            self.cursor_controller.cursor_y = y;
+            self.cursor_controller.selection_anchor =
+                anchor.map(|anchor| self.caret_position(anchor));
+            let before = self.caret_offset((0, self.editor_rows.number_of_rows()));
+            edit(self);
+            let after = self.caret_offset((0, self.editor_rows.number_of_rows()));
+            carets[i] = (
+                self.caret_offset((
+                    self.cursor_controller.cursor_x,
+                    self.cursor_controller.cursor_y,
+                )),
+                None,
+            );
+            for (at, anchor) in &mut carets[i + 1..] {
+                *at = (*at + after).saturating_sub(before);
+                *anchor = anchor.map(|anchor| (anchor + after).saturating_sub(before));
+            }
+        }
+        self.cursor_controller.clear_selection();
+        let (main, _) = carets[main_index];
+        let (x, y) = self.caret_position(main);
+        self.cursor_controller.cursor_x = x;
+        self.cursor_controller.cursor_y = y;
+        let mut offsets = carets
+            .into_iter()
+            .map(|(at, _)| at)
+            .filter(|at| *at != main)
+            .collect::<Vec<_>>();
+        offsets.sort_unstable();
+        offsets.dedup();
+        self.extra_carets = offsets
+            .into_iter()
+            .map(|at| Caret {
+                position: self.caret_position(at),
+                anchor: None,
+            })
+            .collect();
+    }
+
+    /// Adds a caret at the next occurrence of the selection, or of the whole word
+    /// under the cursor, after the last caret added. The caret lands at the same
+    /// position within the occurrence and gets a matching selection.
+    pub fn add_caret_at_next_match(&mut self) {
+        self.editor_rows.load_all(self.syntax_highlight.as_deref());
+        let (cursor_x, cursor_y) = (
+            self.cursor_controller.cursor_x,
+            self.cursor_controller.cursor_y,
+        );
+        let selection = self
+            .cursor_controller
+            .selection()
+            .filter(|((_, start_y), (_, end_y))| start_y == end_y);
+        let (start, end, whole_word) = match selection {
+            Some(((start, _), (end, _))) => (start, end, false),
+            None => match self.word_at_cursor() {
+                Some((start, end)) => (start, end, true),
+                None => {
+                    self.alert("No word under the cursor".into());
+                    return;
+                }
+            },
+        };
+        let row = self.editor_rows.get_editor_row(cursor_y);
+        let word = row.row_content[row.byte_index(start)..row.byte_index(end)].to_string();
+        let within = cursor_x - start;
+        let anchor_within =
+            selection.map(|_| self.cursor_controller.selection_anchor.unwrap().0 - start);
+        let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        // Starts of the occurrences, as character positions.
+        let matches = self
+            .editor_rows
+            .row_contents
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                let content = &row.row_content;
+                content
+                    .match_indices(word.as_str())
+                    .filter(|(index, _)| {
+                        !whole_word
+                            || (!is_word(content[..*index].chars().next_back())
+                                && !is_word(content[index + word.len()..].chars().next()))
+                    })
+                    .map(|(index, _)| (content[..index].chars().count(), y))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let last = self
+            .extra_carets
+            .last()
+            .map_or((cursor_x, cursor_y), |caret| caret.position);
+        let taken = |(x, y): (usize, usize)| {
+            (x + within, y) == (cursor_x, cursor_y)
+                || self
+                    .extra_carets
+                    .iter()
+                    .any(|caret| caret.position == (x + within, y))
+        };
+        let next = matches
+            .iter()
+            .find(|&&(x, y)| (y, x + within) > (last.1, last.0) && !taken((x, y)))
+            .or_else(|| matches.iter().find(|&&start| !taken(start)));
+        match next {
+            Some(&(x, y)) => {
+                self.extra_carets.push(Caret {
+                    position: (x + within, y),
+                    anchor: anchor_within.map(|within| (x + within, y)),
+                });
+                self.status_message
+                    .set_message(format!("{} cursors", self.extra_carets.len() + 1));
+            }
+            None => self.alert(format!("No more matches for {}", word)),
+        }
+    }
+
+    /// Adds a caret on the row below the lowest caret, in the cursor's column.
+    pub fn add_caret_below(&mut self) {
+        let y = self
+            .extra_carets
+            .iter()
+            .map(|caret| caret.position.1)
+            .fold(self.cursor_controller.cursor_y, cmp::max)
+            + 1;
+        if y >= self.editor_rows.number_of_rows() {
+            self.alert("No line below".into());
+            return;
+        }
+        let x = cmp::min(
+            self.cursor_controller.cursor_x,
+            self.editor_rows.get_editor_row(y).char_count(),
+        );
+        self.extra_carets.push(Caret {
+            position: (x, y),
+            anchor: None,
+        });
+        self.status_message
+            .set_message(format!("{} cursors", self.extra_carets.len() + 1));
+    }
+
+    pub fn clear_carets(&mut self) {
+        self.extra_carets.clear();
+    }
+
+    pub fn repeat_last_edit(&mut self) {
+        match self.last_edit {
+            Some(edit) => self.apply_edit(edit),
+            None => self.status_message.set_message("Nothing to repeat".into()),
+        }
+    }
+
+    /// Keeps row-indexed state in sync after `removed` rows starting at `at` were
+    /// replaced by `inserted` new ones. Rows inside the replaced range keep their
+    /// index if it still exists and are dropped otherwise.
+    fn rows_changed(&mut self, at: usize, removed: usize, inserted: usize) {
+        self.bookmarks = self
+            .bookmarks
+            .iter()
+            .filter_map(|&row| {
+                if row < at {
+                    Some(row)
+                } else if row >= at + removed {
+                    Some(row - removed + inserted)
+                } else if row - at < inserted {
+                    Some(row)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        self.editor_rows.folds = self
+            .editor_rows
+            .folds
+            .iter()
+            .filter_map(|(&start, &end)| {
+                if at + removed <= start {
+                    Some((start - removed + inserted, end - removed + inserted))
+                } else if at > end {
+                    Some((start, end))
+                } else {
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// Folds the indented block below the cursor row, or unfolds it if already folded.
+    pub fn toggle_fold(&mut self) {
+        let at = self.cursor_controller.cursor_y;
+        if self.editor_rows.folds.remove(&at).is_some() {
+            return;
+        }
+        let hidden = self.editor_rows.fold(at);
+        if hidden == 0 {
+            self.status_message.set_message("Nothing to fold".into());
         }
-        self.dirty += 1;
     }
 
-    pub fn insert_newline(&mut self) {
-        if self.editor_rows.edit_mode == EditMode::READONLY {
-            self.status_message
-                .set_message("Failed to edit readonly buffer".into());
+    pub fn toggle_bookmark(&mut self) {
+        let at = self.cursor_controller.cursor_y;
+        if at >= self.editor_rows.number_of_rows() {
             return;
         }
+        if !self.bookmarks.remove(&at) {
+            self.bookmarks.insert(at);
+        }
+    }
 
-        if self.cursor_controller.cursor_x == 0 {
-            self.editor_rows
-                .insert_row(self.cursor_controller.cursor_y, String::new())
-        } else {
-            let current_row = self
-                .editor_rows
-                .get_editor_row_mut(self.cursor_controller.cursor_y);
-            let new_row_content = current_row.row_content[self.cursor_controller.cursor_x..].into();
-            current_row
-                .row_content
-                .truncate(self.cursor_controller.cursor_x);
-            EditorRows::render_row(current_row);
-            self.editor_rows
-                .insert_row(self.cursor_controller.cursor_y + 1, new_row_content);
-            if let Some(it) = self.syntax_highlight.as_ref() {
-                it.update_syntax(
-                    self.cursor_controller.cursor_y,
-                    &mut self.editor_rows.row_contents,
-                );
-                it.update_syntax(
-                    self.cursor_controller.cursor_y + 1,
-                    &mut self.editor_rows.row_contents,
-                )
+    /// Moves the cursor to the next (or previous) bookmark, wrapping around.
+    pub fn jump_to_bookmark(&mut self, direction: SearchDirection) {
+        let at = self.cursor_controller.cursor_y;
+        let target = match direction {
+            SearchDirection::Forward => self
+                .bookmarks
+                .range(at + 1..)
+                .next()
+                .or_else(|| self.bookmarks.iter().next()),
+            SearchDirection::Backward => self
+                .bookmarks
+                .range(..at)
+                .next_back()
+                .or_else(|| self.bookmarks.iter().next_back()),
+        };
+        match target.copied() {
+            Some(row) => {
+                self.cursor_controller.clear_selection();
+                self.cursor_controller.cursor_y = row;
+                self.cursor_controller.cursor_x = 0;
             }
+            None => self.status_message.set_message("No bookmarks".into()),
         }
-        self.cursor_controller.cursor_x = 0;
-        self.cursor_controller.cursor_y += 1;
-        self.dirty += 1;
     }
 
-    pub fn insert_char(&mut self, ch: char) {
-        if self.editor_rows.edit_mode == EditMode::READONLY {
-            self.status_message
-                .set_message("Failed to edit readonly buffer".into());
-            return;
+    /// Moves the cursor to the first (or last) row of its indentation block.
+    pub fn jump_to_block_edge(&mut self, direction: SearchDirection) {
+        let (start, end) = match self
+            .editor_rows
+            .indent_block(self.cursor_controller.cursor_y)
+        {
+            Some(block) => block,
+            None => return,
+        };
+        let at = match direction {
+            SearchDirection::Forward => end,
+            SearchDirection::Backward => start,
+        };
+        self.cursor_controller.clear_selection();
+        self.cursor_controller.cursor_y = at;
+        self.cursor_controller.cursor_x = self
+            .editor_rows
+            .get_row(at)
+            .chars()
+            .take_while(|ch| ch.is_whitespace())
+            .count();
+    }
+
+    /// Width of the gutter drawn to the left of the text, which only appears while
+    /// there is something to show in it.
+    pub fn gutter_width(&self) -> usize {
+        let changed = self
+            .line_changes
+            .as_ref()
+            .is_some_and(|changes| changes.iter().any(Option::is_some));
+        if self.bookmarks.is_empty() && !changed {
+            0
+        } else {
+            2
         }
+    }
 
-        if self.cursor_controller.cursor_y == self.editor_rows.number_of_rows() {
-            self.editor_rows
-                .insert_row(self.editor_rows.number_of_rows(), String::new());
-            self.dirty += 1;
+    /// Diffs the rows against the baseline unless nothing changed since the last diff.
+    fn update_line_changes(&mut self) {
+        if self.line_changes.is_some() {
+            return;
         }
-        self.editor_rows
-            .get_editor_row_mut(self.cursor_controller.cursor_y)
-            .insert_char(self.cursor_controller.cursor_x, ch);
-        if let Some(it) = self.syntax_highlight.as_ref() {
-            it.update_syntax(
-                self.cursor_controller.cursor_y,
-                &mut self.editor_rows.row_contents,
+        self.line_changes = Some(if self.editor_rows.file_type == FileType::DIR {
+            Vec::new()
+        } else {
+            line_diff::line_changes(
+                &self.editor_rows.baseline,
+                self.editor_rows
+                    .row_contents
+                    .iter()
+                    .map(|row| row.row_content.as_str()),
             )
+        });
+    }
+
+    fn draw_gutter(&mut self, file_row: usize) {
+        let gutter_width = self.gutter_width();
+        if gutter_width == 0 {
+            return;
         }
-        self.cursor_controller.cursor_x += 1;
+        if self.bookmarks.contains(&file_row) {
+            queue!(self.editor_contents, SetForegroundColor(BOOKMARK_COLOR)).unwrap();
+            self.editor_contents.push('*');
+            queue!(self.editor_contents, SetForegroundColor(Color::Reset)).unwrap();
+        } else {
+            self.editor_contents.push(' ');
+        }
+        let change = self
+            .line_changes
+            .as_ref()
+            .and_then(|changes| changes.get(file_row).copied().flatten());
+        match change {
+            Some(change) => {
+                let color = match change {
+                    LineChange::Added => ADDED_LINE_COLOR,
+                    LineChange::Modified => MODIFIED_LINE_COLOR,
+                };
+                queue!(self.editor_contents, SetForegroundColor(color)).unwrap();
+                self.editor_contents.push('\u{2502}');
+                queue!(self.editor_contents, SetForegroundColor(Color::Reset)).unwrap();
+            }
+            None => self.editor_contents.push(' '),
+        }
+        (2..gutter_width).for_each(|_| self.editor_contents.push(' '));
+    }
+
+    /// Shows `message` and signals the failure with the configured bell.
+    pub fn alert(&mut self, message: String) {
+        self.status_message.set_message(message);
+        self.ring_bell();
+    }
+
+    pub fn ring_bell(&self) {
+        // A bell that can't be written isn't worth interrupting the edit over.
+        let _ = match self.bell {
+            Bell::None => Ok(()),
+            Bell::Audible => execute!(stdout(), style::Print('\x07')),
+            Bell::Visual => execute!(stdout(), style::Print("\x1b[?5h")).and_then(|_| {
+                thread::sleep(FLASH_DURATION);
+                execute!(stdout(), style::Print("\x1b[?5l"))
+            }),
+        };
+    }
+
+    pub fn mark_dirty(&mut self) {
         self.dirty += 1;
+        self.word_count = None;
+        self.line_changes = None;
+    }
+
+    /// Returns the `(words, characters)` totals for the buffer, only
+    /// recounting after the cache has been invalidated by an edit.
+    pub fn word_count(&mut self) -> (usize, usize) {
+        let editor_rows = &self.editor_rows;
+        *self.word_count.get_or_insert_with(|| {
+            editor_rows
+                .row_contents
+                .iter()
+                .fold((0, 0), |(words, chars), row| {
+                    (
+                        words + row.row_content.split_whitespace().count(),
+                        chars + row.row_content.chars().count(),
+                    )
+                })
+        })
     }
 
     pub fn draw_status_bar(&mut self) {
         self.editor_contents
             .push_str(&style::Attribute::Reverse.to_string());
+        let buffer_info = if self.number_of_buffers() > 1 {
+            format!(
+                "[{}/{}] ",
+                self.current_buffer + 1,
+                self.number_of_buffers()
+            )
+        } else {
+            String::new()
+        };
         let info = format!(
-            "{} {} -- {} lines",
+            "{}{} {} -- {}{} lines{}",
+            buffer_info,
             self.editor_rows
                 .filename
                 .as_ref()
                 .and_then(|path| path.file_name())
                 .and_then(|name| name.to_str())
                 .unwrap_or("[No Name]"),
-            if self.dirty > 0 { "(modified)" } else { "" },
-            self.editor_rows.number_of_rows()
+            if self.dirty > 0 {
+                "(modified)"
+            } else if self.editor_rows.edit_mode == EditMode::READONLY {
+                "(read-only)"
+            } else {
+                ""
+            },
+            self.editor_rows.number_of_rows(),
+            if self.editor_rows.is_fully_loaded() {
+                ""
+            } else {
+                "+"
+            },
+            if self.editor_rows.missing_final_newline() {
+                " NoEOL"
+            } else {
+                ""
+            }
         );
-        let info_len = cmp::min(info.len(), self.win_size.0);
+        let counts = if self.show_word_count {
+            let (words, chars) = self.word_count();
+            format!(" | {} words, {} chars", words, chars)
+        } else {
+            String::new()
+        };
+        let clock = if self.show_clock {
+            format!(" | {}", timestamp::now(CLOCK_FORMAT))
+        } else {
+            String::new()
+        };
+        let number_of_rows = self.editor_rows.number_of_rows();
+        let percentage = (cmp::min(self.cursor_controller.cursor_y + 1, number_of_rows) * 100)
+            .checked_div(number_of_rows)
+            .unwrap_or(0);
         /* modify the following */
-        let line_info = format!(
-            "{} | {}/{}",
+        let mut line_info = format!(
+            "{} | {} | {} {}{} | Ln {}, Col {} -- {}%",
+            if self.overwrite { "OVR" } else { "INS" },
             self.syntax_highlight
                 .as_ref()
                 .map(|highlight| highlight.file_type())
                 .unwrap_or("no ft"),
+            self.editor_rows.encoding.name(),
+            self.editor_rows.line_ending.name(),
+            counts,
             self.cursor_controller.cursor_y + 1,
-            self.editor_rows.number_of_rows()
+            self.cursor_controller.render_x + 1,
+            percentage
         );
-        self.editor_contents.push_str(&info[..info_len]);
-        for i in info_len..self.win_size.0 {
-            if self.win_size.0 - i == line_info.len() {
-                self.editor_contents.push_str(&line_info);
-                break;
-            } else {
-                self.editor_contents.push(' ')
-            }
+        // The clock is the first to go when the bar runs out of space.
+        if info.chars().count() + line_info.chars().count() + clock.chars().count()
+            <= self.win_size.0
+        {
+            line_info.push_str(&clock);
         }
+        // When the bar is too narrow, the file info is cut short first, keeping a space
+        // before the line info, then the start of the line info.
+        let line_info_len = line_info.chars().count();
+        let line_info_start = line_info_len.saturating_sub(self.win_size.0);
+        let info_space = self.win_size.0 - (line_info_len - line_info_start);
+        let info_len = match info.chars().count() {
+            len if len > info_space => info_space.saturating_sub(1),
+            len => len,
+        };
+        self.editor_contents
+            .push_str(&info.chars().take(info_len).collect::<String>());
+        (info_len..info_space).for_each(|_| self.editor_contents.push(' '));
+        self.editor_contents
+            .push_str(&line_info.chars().skip(line_info_start).collect::<String>());
         self.editor_contents
             .push_str(&style::Attribute::Reset.to_string());
         self.editor_contents.push_str("\r\n");
     }
 
+    /// Draws the part of a row from render position `line_start` that fits on one
+    /// screen line, which is where the row starts unless it is soft-wrapped, with
+    /// `line_end` being where the next screen line of a wrapped row starts. Only that
+    /// part of the row is walked, so very long rows draw as fast as short ones.
+    ///
+    /// Background colors (overflow, ruler, matching brackets, selection) are layered
+    /// on top of the syntax colors, later entries taking precedence.
+    fn draw_row(
+        &mut self,
+        file_row: usize,
+        line_start: usize,
+        line_end: Option<usize>,
+        brackets: &[(usize, usize)],
+    ) {
+        if line_start == 0 {
+            self.draw_gutter(file_row);
+        } else {
+            (0..self.gutter_width()).for_each(|_| self.editor_contents.push(' '));
+        }
+        let screen_columns = self.cursor_controller.screen_columns;
+        let row = self.editor_rows.get_editor_row(file_row);
+        let column_offset = self.cursor_controller.column_offset + line_start;
+        let len = cmp::min(
+            line_end
+                .unwrap_or(row.render.len())
+                .saturating_sub(column_offset),
+            screen_columns,
+        );
+        let start = if len == 0 { 0 } else { column_offset };
+        let end = start + len;
+        // Markers take the first and last columns when the row goes on past them.
+        let clipped_left = self.cursor_controller.column_offset > 0 && !row.render.is_empty();
+        let clipped_right =
+            line_end.is_none() && len > 1 && row.render.len() > column_offset + screen_columns;
+        let start = if clipped_left && len > 0 {
+            start + 1
+        } else {
+            start
+        };
+        let end = if clipped_right { end - 1 } else { end };
+        let visible = row
+            .render
+            .chars()
+            .skip(start)
+            .take(end - start)
+            .collect::<Vec<_>>();
+        let mut backgrounds = Vec::new();
+        if let Some(width) = row.mixed_indent_width() {
+            backgrounds.push((0, width, MIXED_INDENT_COLOR));
+        }
+        if let Some(max_length) = self.max_line_length {
+            backgrounds.push((max_length, row.render.len(), LONG_LINE_COLOR));
+        }
+        if self.show_trailing_whitespace {
+            if let Some(trailing) = row.trailing_whitespace_start() {
+                backgrounds.push((trailing, row.render.len(), TRAILING_WHITESPACE_COLOR));
+            }
+        }
+        if let Some(ruler) = self.ruler {
+            backgrounds.push((ruler, ruler + 1, RULER_COLOR));
+        }
+        for (x, _) in brackets.iter().filter(|(_, y)| *y == file_row) {
+            let render_x = row.get_render_x(*x);
+            backgrounds.push((render_x, render_x + 1, BRACKET_COLOR));
+        }
+        for caret in &self.extra_carets {
+            if let Some((anchor_x, anchor_y)) = caret.anchor {
+                if (anchor_y, caret.position.1) == (file_row, file_row) {
+                    let (from, to) = (
+                        cmp::min(anchor_x, caret.position.0),
+                        cmp::max(anchor_x, caret.position.0),
+                    );
+                    backgrounds.push((
+                        row.get_render_x(from),
+                        row.get_render_x(to),
+                        SELECTION_COLOR,
+                    ));
+                }
+            }
+        }
+        for caret in self
+            .extra_carets
+            .iter()
+            .filter(|caret| caret.position.1 == file_row)
+        {
+            let render_x = row.get_render_x(caret.position.0);
+            backgrounds.push((render_x, render_x + 1, CARET_COLOR));
+        }
+        if let Some((from, to)) = self.cursor_controller.selected_render_range(file_row, row) {
+            backgrounds.push((from, to, SELECTION_COLOR));
+        }
+        let mut bounds = vec![start, end];
+        backgrounds.iter().for_each(|(from, to, _)| {
+            bounds.extend([(*from).clamp(start, end), (*to).clamp(start, end)])
+        });
+        bounds.sort_unstable();
+        bounds.dedup();
+        if clipped_left {
+            queue!(
+                self.editor_contents,
+                SetForegroundColor(CLIPPED_MARKER_COLOR)
+            )
+            .unwrap();
+            self.editor_contents.push('<');
+            queue!(self.editor_contents, SetForegroundColor(Color::Reset)).unwrap();
+        }
+        for window in bounds.windows(2) {
+            let (from, to) = (window[0], window[1]);
+            let background = backgrounds
+                .iter()
+                .rev()
+                .find(|(bg_from, bg_to, _)| *bg_from <= from && from < *bg_to)
+                .map(|(_, _, color)| *color);
+            if let Some(color) = background {
+                queue!(self.editor_contents, SetBackgroundColor(color)).unwrap();
+            }
+            let render = visible
+                .iter()
+                .skip(from - start)
+                .take(to - from)
+                .collect::<String>();
+            self.syntax_highlight
+                .as_ref()
+                .map(|syntax_highlight| {
+                    syntax_highlight.color_row(
+                        &render,
+                        &row.highlight[from..cmp::min(to, row.highlight.len())],
+                        &mut self.editor_contents,
+                    )
+                })
+                .unwrap_or_else(|| self.editor_contents.push_str(&render));
+            if background.is_some() {
+                queue!(self.editor_contents, SetBackgroundColor(Color::Reset)).unwrap();
+            }
+        }
+        if clipped_right {
+            queue!(
+                self.editor_contents,
+                SetForegroundColor(CLIPPED_MARKER_COLOR)
+            )
+            .unwrap();
+            self.editor_contents.push('>');
+            queue!(self.editor_contents, SetForegroundColor(Color::Reset)).unwrap();
+        }
+        if line_end.is_some() {
+            return;
+        }
+        // Carets past the last character get a blank cell of their own.
+        let mut len = cmp::max(len, usize::from(clipped_left));
+        if len < screen_columns
+            && row.render.len() >= column_offset
+            && self.extra_carets.iter().any(|caret| {
+                let (x, y) = caret.position;
+                y == file_row && row.get_render_x(x) >= row.render.len()
+            })
+        {
+            queue!(self.editor_contents, SetBackgroundColor(CARET_COLOR)).unwrap();
+            self.editor_contents.push(' ');
+            queue!(self.editor_contents, SetBackgroundColor(Color::Reset)).unwrap();
+            len += 1;
+        }
+        if let Some(end) = self.editor_rows.folds.get(&file_row) {
+            let summary = format!(" \u{2026} {} lines", end - file_row)
+                .chars()
+                .take(screen_columns - len)
+                .collect::<String>();
+            queue!(self.editor_contents, SetForegroundColor(FOLD_COLOR)).unwrap();
+            self.editor_contents.push_str(&summary);
+            queue!(self.editor_contents, SetForegroundColor(Color::Reset)).unwrap();
+            return;
+        }
+        if let Some(ruler_x) = self
+            .ruler
+            .and_then(|ruler| ruler.checked_sub(column_offset))
+            .filter(|ruler_x| *ruler_x >= len && *ruler_x < screen_columns)
+        {
+            (len..ruler_x).for_each(|_| self.editor_contents.push(' '));
+            queue!(self.editor_contents, SetBackgroundColor(RULER_COLOR)).unwrap();
+            self.editor_contents.push(' ');
+            queue!(self.editor_contents, SetBackgroundColor(Color::Reset)).unwrap();
+        }
+    }
+
+    fn draw_overlay_row(&mut self, index: usize) {
+        let line = self.overlay[index]
+            .chars()
+            .take(self.win_size.0)
+            .collect::<String>();
+        if index == self.overlay_selected {
+            self.editor_contents
+                .push_str(&style::Attribute::Reverse.to_string());
+            self.editor_contents.push_str(&line);
+            self.editor_contents
+                .push_str(&style::Attribute::Reset.to_string());
+        } else {
+            self.editor_contents.push_str(&line);
+        }
+    }
+
     pub fn draw_rows(&mut self) {
+        let (cursor_x, cursor_y) = (
+            self.cursor_controller.cursor_x,
+            self.cursor_controller.cursor_y,
+        );
+        let brackets = self
+            .editor_rows
+            .find_matching_bracket(cursor_x, cursor_y)
+            .map_or_else(Vec::new, |matching| vec![(cursor_x, cursor_y), matching]);
         let screen_rows = self.win_size.1;
         let screen_columns = self.win_size.0;
+        let mut file_row = self.cursor_controller.row_offset;
+        // Screen line of `file_row` to draw next, when it is soft-wrapped.
+        let mut wrap_line = 0;
+        let mut starts = Vec::new();
+        let overlay_start = screen_rows.saturating_sub(self.overlay.len());
         for i in 0..screen_rows {
-            let file_row = i + self.cursor_controller.row_offset;
-            if file_row >= self.editor_rows.number_of_rows() {
+            if i >= overlay_start {
+                self.draw_overlay_row(i - overlay_start);
+            } else if file_row >= self.editor_rows.number_of_rows() {
                 if self.editor_rows.number_of_rows() == 0 && i == screen_rows / 3 {
                     let mut welcome = format!("Pound Editor --- Version {}", VERSION);
                     if welcome.len() > screen_columns {
@@ -499,22 +3084,22 @@ impl Output {
                     self.editor_contents.push('~');
                 }
             } else {
-                let row = self.editor_rows.get_editor_row(file_row);
-                let render = &row.render;
-                let column_offset = self.cursor_controller.column_offset;
-                let len = cmp::min(render.len().saturating_sub(column_offset), screen_columns);
-                let start = if len == 0 { 0 } else { column_offset };
-                let render = render.chars().skip(start).take(len).collect::<String>();
-                self.syntax_highlight
-                    .as_ref()
-                    .map(|syntax_highlight| {
-                        syntax_highlight.color_row(
-                            &render,
-                            &row.highlight[start..cmp::min(start + len, row.highlight.len())],
-                            &mut self.editor_contents,
-                        )
-                    })
-                    .unwrap_or_else(|| self.editor_contents.push_str(&render));
+                if wrap_line == 0 {
+                    starts = self
+                        .cursor_controller
+                        .wrap_starts(file_row, &self.editor_rows);
+                }
+                self.draw_row(
+                    file_row,
+                    starts[wrap_line],
+                    starts.get(wrap_line + 1).copied(),
+                    &brackets,
+                );
+                wrap_line += 1;
+                if wrap_line == starts.len() {
+                    wrap_line = 0;
+                    file_row = self.editor_rows.next_visible_row(file_row);
+                }
             }
             queue!(
                 self.editor_contents,
@@ -526,18 +3111,167 @@ impl Output {
     }
 
     pub fn move_cursor(&mut self, direction: KeyCode) {
+        self.cursor_controller.clear_selection();
+        let rows = &self.editor_rows;
+        let mut caret_controller = self.cursor_controller;
+        self.extra_carets
+            .retain(|caret| caret.position.1 < rows.number_of_rows());
+        self.extra_carets.iter_mut().for_each(|caret| {
+            let (x, y) = caret.position;
+            caret_controller.cursor_x = cmp::min(x, rows.get_editor_row(y).char_count());
+            caret_controller.cursor_y = y;
+            caret_controller.move_cursor(direction, rows);
+            *caret = Caret {
+                position: (caret_controller.cursor_x, caret_controller.cursor_y),
+                anchor: None,
+            };
+        });
         self.cursor_controller
             .move_cursor(direction, &self.editor_rows);
     }
 
-    pub fn refresh_screen(&mut self) -> crossterm::Result<()> {
+    pub fn select(&mut self, direction: KeyCode) {
+        self.extra_carets.clear();
+        self.cursor_controller.start_selection();
+        self.cursor_controller
+            .move_cursor(direction, &self.editor_rows);
+    }
+
+    pub fn click(&mut self, column: usize, row: usize) {
+        self.extra_carets.clear();
+        self.cursor_controller.clear_selection();
+        self.cursor_controller.move_to_screen_position(
+            column.saturating_sub(self.gutter_width()),
+            row,
+            &self.editor_rows,
+        );
+    }
+
+    pub fn scroll_view(&mut self, direction: SearchDirection) {
+        self.cursor_controller
+            .scroll_view(direction, &self.editor_rows);
+    }
+
+    pub fn scroll_half_page(&mut self, direction: SearchDirection) {
+        self.cursor_controller.clear_selection();
+        self.cursor_controller
+            .scroll_half_page(direction, &self.editor_rows);
+    }
+
+    pub fn toggle_ruler(&mut self) {
+        self.ruler = match self.ruler {
+            Some(_) => None,
+            None => Some(self.ruler_column),
+        };
+    }
+
+    /// Switches whether ignored files are listed, relisting a shown directory.
+    pub fn toggle_ignored_files(&mut self) -> crossterm::Result<()> {
+        self.show_ignored = !self.show_ignored;
+        self.status_message.set_message(if self.show_ignored {
+            "Showing ignored files".into()
+        } else {
+            "Hiding ignored files".into()
+        });
+        if let Some(dir) = self.editor_rows.listed_dir.clone() {
+            self.open_file(dir)?;
+        }
+        Ok(())
+    }
+
+    pub fn toggle_trailing_whitespace(&mut self) {
+        self.show_trailing_whitespace = !self.show_trailing_whitespace;
+    }
+
+    pub fn toggle_comment_continuation(&mut self) {
+        self.continue_comments = !self.continue_comments;
+        self.status_message.set_message(format!(
+            "Comment continuation {}",
+            if self.continue_comments { "on" } else { "off" }
+        ));
+    }
+
+    pub fn toggle_long_lines(&mut self) {
+        self.max_line_length = match self.max_line_length {
+            Some(_) => None,
+            None => Some(self.long_line_length),
+        };
+    }
+
+    /// Scrolls to the cursor and draws the text area, status bar and message bar.
+    fn draw_frame(&mut self) {
+        let loaded = self.editor_rows.number_of_rows();
+        let unreadable = self.editor_rows.read_error.is_some();
+        self.editor_rows.load_rows(
+            cmp::max(
+                self.cursor_controller.cursor_y,
+                self.cursor_controller.row_offset,
+            ) + 2 * self.win_size.1,
+            self.syntax_highlight.as_deref(),
+        );
+        if self.editor_rows.number_of_rows() != loaded {
+            self.line_changes = None;
+        }
+        if let (false, Some(err)) = (unreadable, &self.editor_rows.read_error) {
+            self.status_message
+                .set_message(format!("Cannot read the rest of the file: {}", err));
+        }
+        self.update_line_changes();
+        self.cursor_controller.screen_columns = self.win_size.0.saturating_sub(self.gutter_width());
         self.cursor_controller.scroll(&self.editor_rows);
-        queue!(self.editor_contents, cursor::Hide, cursor::MoveTo(0, 0))?;
         self.draw_rows();
         self.draw_status_bar();
         self.draw_message_bar();
-        let cursor_x = self.cursor_controller.render_x - self.cursor_controller.column_offset;
-        let cursor_y = self.cursor_controller.cursor_y - self.cursor_controller.row_offset;
+    }
+
+    /// Draws the current frame into memory instead of the terminal, returning each
+    /// screen line as it would appear, with colors and other escape sequences removed.
+    pub fn render(&mut self) -> Vec<String> {
+        self.draw_frame();
+        let frame = self.editor_contents.take();
+        let mut text = String::new();
+        let mut chars = frame.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\x1b' {
+                text.push(ch);
+            } else if chars.next() == Some('[') {
+                // A control sequence runs up to its final byte in `@..=~`.
+                chars.find(|ch| ('@'..='~').contains(ch));
+            }
+        }
+        text.split("\r\n").map(String::from).collect()
+    }
+
+    /// The cursor shape for the shown buffer's current editing mode.
+    fn cursor_shape(&self) -> CursorShape {
+        if self.editor_rows.edit_mode == EditMode::READONLY {
+            self.cursor_shapes.read_only
+        } else if self.overwrite {
+            self.cursor_shapes.overwrite
+        } else {
+            self.cursor_shapes.insert
+        }
+    }
+
+    pub fn refresh_screen(&mut self) -> crossterm::Result<()> {
+        queue!(self.editor_contents, cursor::Hide, cursor::MoveTo(0, 0))?;
+        let shape = self.cursor_shape();
+        if self.shown_cursor_shape != Some(shape) {
+            queue!(self.editor_contents, cursor::SetCursorShape(shape))?;
+            self.shown_cursor_shape = Some(shape);
+        }
+        self.draw_frame();
+        let (cursor_x, cursor_y) = match self.prompt_caret {
+            Some(caret) => (
+                cmp::min(caret, self.win_size.0.saturating_sub(1)),
+                self.win_size.1 + 1,
+            ),
+            None => {
+                let (cursor_x, cursor_y) =
+                    self.cursor_controller.screen_position(&self.editor_rows);
+                (cursor_x + self.gutter_width(), cursor_y)
+            }
+        };
         queue!(
             self.editor_contents,
             cursor::MoveTo(cursor_x as u16, cursor_y as u16),
@@ -546,3 +3280,327 @@ impl Output {
         self.editor_contents.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output_with(contents: &str) -> Output {
+        let mut output = Output::with_size((40, 10));
+        output.editor_rows = EditorRows::from_str(contents, &mut None);
+        output
+    }
+
+    #[test]
+    fn copies_selection_with_multibyte_characters() {
+        let mut output = output_with("héllo wörld\nsécond\n");
+        output.cursor_controller.cursor_x = 1;
+        output.select(KeyCode::Right);
+        output.select(KeyCode::Right);
+        output.select(KeyCode::Right);
+        output.select(KeyCode::Right);
+        assert_eq!(output.selected_text().as_deref(), Some("éllo"));
+        output.select(KeyCode::Down);
+        output.select(KeyCode::End);
+        output.copy_selection();
+        assert_eq!(output.clipboard.as_deref(), Some("éllo wörld\nsécond"));
+    }
+
+    #[test]
+    fn cycles_case_of_multibyte_word() {
+        let mut output = output_with("héllo wörld\n");
+        output.cursor_controller.cursor_x = 8;
+        output.cycle_case();
+        assert_eq!(output.editor_rows.get_row(0), "héllo Wörld");
+        output.cycle_case();
+        assert_eq!(output.editor_rows.get_row(0), "héllo WÖRLD");
+        output.cycle_case();
+        assert_eq!(output.editor_rows.get_row(0), "héllo wörld");
+    }
+
+    #[test]
+    fn keeps_selection_end_when_case_changes_length() {
+        let mut output = output_with("straße é\n");
+        output.select(KeyCode::End);
+        output.cycle_case();
+        assert_eq!(output.editor_rows.get_row(0), "Straße É");
+        output.cycle_case();
+        assert_eq!(output.editor_rows.get_row(0), "STRASSE É");
+        assert_eq!(output.cursor_controller.cursor_x, 9);
+        assert_eq!(output.selected_text().as_deref(), Some("STRASSE É"));
+    }
+
+    #[test]
+    fn renames_selected_word_at_every_caret() {
+        let mut output = output_with("let naïve = 1;\nnaïve + naïves + naïve\n");
+        output.cursor_controller.cursor_x = 4;
+        (0..5).for_each(|_| output.select(KeyCode::Right));
+        output.add_caret_at_next_match();
+        output.add_caret_at_next_match();
+        output.add_caret_at_next_match();
+        assert_eq!(output.extra_carets.len(), 3);
+        "x".chars()
+            .for_each(|ch| output.apply_edit(EditCommand::InsertChar(ch)));
+        assert_eq!(output.editor_rows.get_row(0), "let x = 1;");
+        assert_eq!(output.editor_rows.get_row(1), "x + xs + x");
+    }
+
+    #[test]
+    fn adds_caret_inside_next_multibyte_word() {
+        let mut output = output_with("é wörld, wörld wörlds\n");
+        output.cursor_controller.cursor_x = 4;
+        output.add_caret_at_next_match();
+        assert_eq!(output.extra_carets[0].position, (11, 0));
+        output.apply_edit(EditCommand::DeleteBackward);
+        assert_eq!(output.editor_rows.get_row(0), "é wrld, wrld wörlds");
+        output.add_caret_at_next_match();
+        assert_eq!(output.extra_carets.len(), 1);
+    }
+
+    #[test]
+    fn reports_copy_command_that_ignores_its_input() {
+        let mut output = output_with(&"é".repeat(1 << 20));
+        output.copy_command = "true".into();
+        output.copy_to_command();
+        let message = output.status_message.message().unwrap();
+        assert!(
+            message.starts_with("true did not take all of the text"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn copies_multibyte_selection_to_command() {
+        let path = env::temp_dir().join(format!("pound-copy-{}", std::process::id()));
+        let mut output = output_with("héllo wörld\n");
+        output.copy_command = format!("cat > {}", path.display());
+        output.cursor_controller.cursor_x = 6;
+        output.select(KeyCode::End);
+        output.copy_to_command();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "wörld");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn filters_multibyte_selection_through_command() {
+        let mut output = output_with("é\nzé\nbé\nâ\n");
+        output.cursor_controller.cursor_y = 1;
+        output.select(KeyCode::Down);
+        output.select(KeyCode::End);
+        output.filter_through("sort");
+        let rows = (0..4)
+            .map(|at| output.editor_rows.get_row(at))
+            .collect::<Vec<_>>();
+        assert_eq!(rows, ["é", "bé", "zé", "â"]);
+    }
+
+    #[test]
+    fn keeps_buffer_when_filter_fails() {
+        let mut output = output_with("b\na\n");
+        output.filter_through("echo oops >&2; exit 3");
+        assert_eq!(output.editor_rows.get_row(0), "b");
+        assert_eq!(
+            output.status_message.message().map(String::as_str),
+            Some("echo oops >&2; exit 3: oops")
+        );
+        output.filter_through("head -c 1");
+        assert_eq!(output.editor_rows.number_of_rows(), 1);
+        assert_eq!(output.editor_rows.get_row(0), "b");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn keeps_buffer_when_directory_cannot_be_read() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("pound-unreadable-dir-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o000)).unwrap();
+        // Permissions don't stop a privileged user, who can list the directory anyway.
+        let unreadable = fs::read_dir(&dir).is_err();
+        let mut output = output_with("kept\n");
+        output.open_file(dir.clone()).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir(&dir).unwrap();
+        if unreadable {
+            assert_eq!(output.editor_rows.get_row(0), "kept");
+            assert!(output
+                .status_message
+                .message()
+                .is_some_and(|message| message.starts_with("Cannot read directory: ")));
+        }
+        // A directory that vanished before it could be listed fails the same way.
+        assert!(EditorRows::from_dir(dir, false).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn edits_the_target_of_a_symlink() {
+        let dir = env::temp_dir().join(format!("pound-symlink-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("target.conf");
+        let link = dir.join("link.conf");
+        let broken = dir.join("broken.conf");
+        fs::write(&target, "value\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing.conf"), &broken).unwrap();
+
+        let mut output = output_with("");
+        output.open_file(link.clone()).unwrap();
+        assert_eq!(
+            output.editor_rows.filename,
+            Some(fs::canonicalize(&target).unwrap())
+        );
+        output.apply_edit(EditCommand::InsertChar('#'));
+        output.save_file().unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "#value\n");
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        output.open_file(broken.clone()).unwrap();
+        assert_eq!(output.editor_rows.get_row(0), "#value");
+        assert!(output
+            .status_message
+            .message()
+            .is_some_and(|message| message.ends_with("broken symbolic link")));
+        assert!(!dir.join("missing.conf").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn renders_the_welcome_screen() {
+        let mut output = Output::with_size((80, 6));
+        assert_eq!(
+            output.render(),
+            [
+                "~",
+                "~",
+                "~                        Pound Editor --- Version 0.0.1",
+                "~",
+                "~",
+                "~",
+                "[No Name]  -- 0 lines                 INS | no ft | utf-8 LF | Ln 1, Col 1 -- 0%",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn shortens_the_status_bar_to_fit() {
+        let status_bar = |columns| {
+            let mut output = Output::with_size((columns, 1));
+            output.editor_rows = EditorRows::from_str("text\n", &mut None);
+            output.editor_rows.filename = Some("notes-for-the-meeting.txt".into());
+            output.render().swap_remove(1)
+        };
+        assert_eq!(
+            status_bar(60),
+            "notes-for-the-m INS | no ft | utf-8 LF | Ln 1, Col 1 -- 100%"
+        );
+        assert_eq!(status_bar(40), "| no ft | utf-8 LF | Ln 1, Col 1 -- 100%");
+    }
+
+    #[test]
+    fn renders_the_word_count_at_80_columns() {
+        let mut output = Output::with_size((80, 1));
+        output.editor_rows = EditorRows::from_str("two words\n", &mut None);
+        output.editor_rows.filename = Some("notes-for-the-meeting.txt".into());
+        output.show_word_count = true;
+        assert_eq!(
+            output.render(),
+            [
+                "two words",
+                "notes-for-the-me INS | no ft | utf-8 LF | 2 words, 9 chars | Ln 1, Col 1 -- 100%",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_the_clock_before_the_file_info() {
+        let status_bar = |columns| {
+            let mut output = Output::with_size((columns, 1));
+            output.editor_rows = EditorRows::from_str("text\n", &mut None);
+            output.editor_rows.filename = Some("notes.txt".into());
+            output.show_clock = true;
+            output.render().swap_remove(1)
+        };
+        assert_eq!(
+            status_bar(70),
+            "notes.txt  -- 1 lines     INS | no ft | utf-8 LF | Ln 1, Col 1 -- 100%"
+        );
+        assert!(status_bar(100).contains("Ln 1, Col 1 -- 100% | "));
+    }
+
+    #[test]
+    fn renders_a_highlighted_file() {
+        let mut output = Output::with_size((80, 3));
+        let mut syntax_highlight = Output::select_syntax("rs");
+        output.editor_rows = EditorRows::from_str("fn main() {}\n", &mut syntax_highlight);
+        output.syntax_highlight = syntax_highlight;
+        assert_eq!(
+            output.render(),
+            [
+                "fn main() {}",
+                "~",
+                "~",
+                "[No Name]  -- 1 lines                INS | rust | utf-8 LF | Ln 1, Col 1 -- 100%",
+                "",
+            ]
+        );
+        output.draw_frame();
+        let frame = output.editor_contents.take();
+        assert!(frame.contains(&format!(
+            "{}fn{} {}main{}",
+            SetForegroundColor(Color::Yellow),
+            SetForegroundColor(Color::Reset),
+            SetForegroundColor(Color::DarkCyan),
+            SetForegroundColor(Color::Reset)
+        )));
+    }
+
+    #[test]
+    fn refuses_to_save_over_a_directory() {
+        let dir = env::temp_dir().join(format!("pound-save-dir-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut output = output_with("text\n");
+        output.editor_rows.filename = Some(dir.clone());
+        output.apply_edit(EditCommand::InsertChar('!'));
+        output.save_file().unwrap();
+        assert_eq!(
+            output.status_message.message().map(String::as_str),
+            Some("Cannot save: path is a directory")
+        );
+        assert!(output.dirty > 0);
+        assert!(dir.is_dir());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn steps_between_matches_with_left_and_right() {
+        let mut output = output_with("ab ab\n");
+        Output::find_callback(&mut output, "ab", KeyCode::Char('b'));
+        assert_eq!(output.cursor_controller.cursor_x, 0);
+        Output::find_callback(&mut output, "ab", KeyCode::Right);
+        assert_eq!(output.cursor_controller.cursor_x, 3);
+        Output::find_callback(&mut output, "ab", KeyCode::Left);
+        assert_eq!(output.cursor_controller.cursor_x, 0);
+    }
+
+    #[test]
+    fn reflows_by_rendered_width() {
+        let mut output = output_with("héé ààà\nööö\n");
+        output.wrap_column = 12;
+        output.reflow_paragraph();
+        assert_eq!(output.editor_rows.get_row(0), "héé ààà ööö");
+        assert_eq!(output.editor_rows.number_of_rows(), 1);
+        let mut output = output_with("\tab cd\n");
+        output.wrap_column = 12;
+        output.reflow_paragraph();
+        assert_eq!(output.editor_rows.get_row(0), "\tab");
+        assert_eq!(output.editor_rows.get_row(1), "\tcd");
+    }
+}
@@ -1,17 +1,25 @@
-use std::time::Duration;
-
 use crossterm::event::{self, Event, KeyEvent};
 
+use crate::POLL_INTERVAL;
+
 pub struct Reader;
 
 impl Reader {
     pub fn read_key(&self) -> crossterm::Result<KeyEvent> {
         loop {
-            if event::poll(Duration::from_millis(500))? {
-                if let Event::Key(event) = event::read()? {
-                    return Ok(event);
-                }
+            if let Some(Event::Key(event)) = self.read_event()? {
+                return Ok(event);
             }
         }
     }
+
+    /// Waits up to `POLL_INTERVAL` for an event, returning `None` if none arrived
+    /// so callers can do periodic work.
+    pub fn read_event(&self) -> crossterm::Result<Option<Event>> {
+        if event::poll(POLL_INTERVAL)? {
+            event::read().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
 }
@@ -5,6 +5,8 @@ pub struct Row {
     pub render: String,
     pub highlight: Vec<HighlightType>,
     pub is_comment: bool, // add line
+    /// Whether the row ends inside a multiline doc comment.
+    pub is_doc_comment: bool,
 }
 
 impl Row {
@@ -14,17 +16,128 @@ impl Row {
             render,
             highlight: Vec::new(),
             is_comment: false, // add line
+            is_doc_comment: false,
         }
     }
 
+    /// Number of characters in the row, which is where `End` puts the cursor.
+    pub fn char_count(&self) -> usize {
+        self.row_content.chars().count()
+    }
+
+    /// Converts the character index `at` into a byte index into `row_content`.
+    pub fn byte_index(&self, at: usize) -> usize {
+        self.row_content
+            .char_indices()
+            .nth(at)
+            .map_or(self.row_content.len(), |(index, _)| index)
+    }
+
+    /// Returns the render width of the row's indentation if it mixes tabs and spaces.
+    pub fn mixed_indent_width(&self) -> Option<usize> {
+        let indent = self
+            .row_content
+            .chars()
+            .take_while(|ch| *ch == ' ' || *ch == '\t')
+            .collect::<String>();
+        if indent.contains(' ') && indent.contains('\t') {
+            Some(self.get_render_x(indent.len()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the render position where the row's trailing spaces and tabs start, if
+    /// it has any.
+    pub fn trailing_whitespace_start(&self) -> Option<usize> {
+        let trimmed = self.render.trim_end_matches([' ', '\t']).chars().count();
+        if trimmed < self.render.chars().count() {
+            Some(trimmed)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `render` is a plain copy of `row_content`, which lets single-character
+    /// edits patch it in place rather than re-rendering the whole row.
+    fn renders_verbatim(&self) -> bool {
+        !self.row_content.contains('\t')
+    }
+
     pub fn insert_char(&mut self, at: usize, ch: char) {
+        let verbatim = self.renders_verbatim() && ch != '\t';
+        let at = self.byte_index(at);
         self.row_content.insert(at, ch);
-        EditorRows::render_row(self)
+        if verbatim {
+            self.render.insert(at, ch)
+        } else {
+            EditorRows::render_row(self)
+        }
+    }
+
+    /// Replaces the character at `at` with `ch`.
+    pub fn replace_char(&mut self, at: usize, ch: char) {
+        let verbatim = self.renders_verbatim() && ch != '\t';
+        let at = self.byte_index(at);
+        let end = at
+            + self.row_content[at..]
+                .chars()
+                .next()
+                .map_or(0, char::len_utf8);
+        self.row_content
+            .replace_range(at..end, ch.encode_utf8(&mut [0; 4]));
+        if verbatim {
+            self.render
+                .replace_range(at..end, ch.encode_utf8(&mut [0; 4]))
+        } else {
+            EditorRows::render_row(self)
+        }
     }
 
     pub fn delete_char(&mut self, at: usize) {
+        let verbatim = self.renders_verbatim();
+        let at = self.byte_index(at);
         self.row_content.remove(at);
-        EditorRows::render_row(self)
+        if verbatim {
+            self.render.remove(at);
+        } else {
+            EditorRows::render_row(self)
+        }
+    }
+
+    pub fn get_render_x(&self, cursor_x: usize) -> usize {
+        self.row_content
+            .chars()
+            .take(cursor_x)
+            .fold(0, |render_x, c| {
+                if c == '\t' {
+                    render_x + (TAB_STOP - 1) - (render_x % TAB_STOP) + 1
+                } else {
+                    render_x + 1
+                }
+            })
+    }
+
+    /// Render positions at which each screen line starts when the row is soft-wrapped to
+    /// `width` columns, breaking after the last space that fits where there is one.
+    pub fn wrap_starts(&self, width: usize) -> Vec<usize> {
+        let mut starts = vec![0];
+        if width == 0 {
+            return starts;
+        }
+        let mut start = 0;
+        // Position just past the last space seen, where a line may break.
+        let mut last_break = 0;
+        for (i, ch) in self.render.chars().enumerate() {
+            if i - start == width {
+                start = if last_break > start { last_break } else { i };
+                starts.push(start);
+            }
+            if ch == ' ' {
+                last_break = i + 1;
+            }
+        }
+        starts
     }
 
     pub fn get_row_content_x(&self, render_x: usize) -> usize {
@@ -1,5 +1,6 @@
 use crate::syntax_highlighting::HighlightType;
 
+#[derive(Copy, Clone)]
 pub enum SearchDirection {
     Forward,
     Backward,
@@ -11,6 +12,14 @@ pub struct SearchIndex {
     pub x_direction: Option<SearchDirection>,
     pub y_direction: Option<SearchDirection>,
     pub previous_highlight: Option<(usize, Vec<HighlightType>)>,
+    /// Whether the current match was reached by wrapping past the end of the buffer.
+    pub wrapped: bool,
+    /// Render position `(x, y)` of the cursor when the search started, where typing
+    /// looks for the first match.
+    pub origin: (usize, usize),
+    /// Render positions `(x, y)` of the start and end of the selection being searched,
+    /// if the search is confined to one.
+    pub bounds: Option<((usize, usize), (usize, usize))>,
 }
 
 impl SearchIndex {
@@ -21,6 +30,9 @@ impl SearchIndex {
             x_direction: None,
             y_direction: None,
             previous_highlight: None,
+            wrapped: false,
+            origin: (0, 0),
+            bounds: None,
         }
     }
 
@@ -29,6 +41,14 @@ impl SearchIndex {
         self.x_index = 0;
         self.y_direction = None;
         self.x_direction = None;
-        self.previous_highlight = None
+        self.previous_highlight = None;
+        self.wrapped = false;
+        self.bounds = None;
+    }
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -1,8 +1,12 @@
 use std::time::{Duration, Instant};
 
+use crate::STATUS_MESSAGE_TIMEOUT;
+
 pub struct StatusMessage {
     message: Option<String>,
     set_time: Option<Instant>,
+    /// How long a message stays visible before `message()` stops returning it.
+    pub timeout: Duration,
 }
 
 impl StatusMessage {
@@ -10,6 +14,7 @@ impl StatusMessage {
         Self {
             message: Some(initial_message),
             set_time: Some(Instant::now()),
+            timeout: STATUS_MESSAGE_TIMEOUT,
         }
     }
 
@@ -20,7 +25,7 @@ impl StatusMessage {
 
     pub fn message(&mut self) -> Option<&String> {
         self.set_time.and_then(|time| {
-            if time.elapsed() > Duration::from_secs(5) {
+            if time.elapsed() > self.timeout {
                 self.message = None;
                 self.set_time = None;
                 None
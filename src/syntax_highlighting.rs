@@ -3,29 +3,82 @@ use crossterm::{
     style::{Color, SetForegroundColor},
 };
 
-use std::cmp;
+use std::{cmp, ops::Range};
 
-use crate::{editor_contents::EditorContents, row::Row};
+use crate::{editor_contents::EditorContents, row::Row, MAX_HIGHLIGHT_LENGTH};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum HighlightType {
     Normal,
     Number,
     SearchMatch,
     String,
     CharLiteral,
+    /// An escape sequence such as `\n` inside a string or character literal.
+    Escape,
     Comment,
     MultilineComment, // add line
+    /// A type-like identifier, guessed from it starting with an uppercase letter.
+    Type,
+    /// The name in a function call or macro invocation.
+    Function,
+    /// A documentation comment, such as Rust's `///` and `/** */`.
+    DocComment,
     Other(Color),
 }
 
 pub trait SyntaxHighlight {
     fn extensions(&self) -> &[&str];
     fn file_type(&self) -> &str;
-    fn comment_start(&self) -> &str;
-    fn multiline_comment(&self) -> Option<(&str, &str)>; // add line
-    fn syntax_color(&self, highlight_type: &HighlightType) -> Color;
-    fn update_syntax(&self, at: usize, editor_rows: &mut Vec<Row>);
+    /// Prefixes that start a line comment; the first one is used when inserting a comment.
+    fn comment_start(&self) -> &[&str];
+    /// Prefixes that turn a comment into a documentation comment.
+    fn doc_comments(&self) -> &[&str] {
+        &[]
+    }
+    fn multiline_comment(&self) -> Option<(&str, &str)> {
+        None
+    }
+    /// Whether identifiers starting with an uppercase letter are highlighted as types.
+    fn camel_case_types(&self) -> bool {
+        false
+    }
+    /// Whether Rust-style raw strings (`r"..."`, `r#"..."#`) are recognized.
+    fn raw_strings(&self) -> bool {
+        false
+    }
+    /// Whether backticks delimit strings, as in Go raw strings and JavaScript templates.
+    fn backtick_strings(&self) -> bool {
+        false
+    }
+    /// Whether keywords match regardless of ASCII case, as in SQL.
+    fn case_insensitive_keywords(&self) -> bool {
+        false
+    }
+    fn syntax_color(&self, highlight_type: &HighlightType) -> Color {
+        match highlight_type {
+            HighlightType::Normal => Color::Reset,
+            HighlightType::Number => Color::Cyan,
+            HighlightType::SearchMatch => Color::Blue,
+            HighlightType::String => Color::Green,
+            HighlightType::CharLiteral => Color::DarkGreen,
+            HighlightType::Escape => Color::DarkYellow,
+            HighlightType::Comment | HighlightType::MultilineComment => Color::DarkGrey,
+            HighlightType::DocComment => Color::Grey,
+            HighlightType::Function => Color::DarkCyan,
+            HighlightType::Type => Color::Magenta,
+            HighlightType::Other(color) => *color,
+        }
+    }
+    /// Highlights the single row at `at`, returning whether its `is_comment`
+    /// state changed (meaning the rows below it may need re-highlighting).
+    fn update_row_syntax(&self, at: usize, editor_rows: &mut [Row]) -> bool;
+    fn update_syntax(&self, at: usize, editor_rows: &mut Vec<Row>) {
+        let mut at = at;
+        while self.update_row_syntax(at, editor_rows) && at + 1 < editor_rows.len() {
+            at += 1;
+        }
+    }
     fn color_row(&self, render: &str, highlight: &[HighlightType], out: &mut EditorContents) {
         let mut current_color = self.syntax_color(&HighlightType::Normal);
         render.char_indices().for_each(|(i, c)| {
@@ -55,17 +108,27 @@ macro_rules! syntax_struct {
             extensions:$ext:expr,
             file_type:$type:expr,
             comment_start:$start:expr,
+            doc_comments:$doc:expr,
             keywords: {
                 $([$color:expr; $($words:expr),*]),*
             },
-            multiline_comment:$ml_comment:expr
+            multiline_comment:$ml_comment:expr,
+            camel_case_types:$camel:expr,
+            raw_strings:$raw:expr,
+            backtick_strings:$backtick:expr,
+            case_insensitive_keywords:$case_insensitive:expr
         }
     ) => {
         pub struct $Name {
             pub extensions: &'static [&'static str],
             pub file_type: &'static str,
-            pub comment_start:&'static str,
-            pub multiline_comment:Option<(&'static str,&'static str)>
+            pub comment_start: &'static [&'static str],
+            pub doc_comments: &'static [&'static str],
+            pub multiline_comment:Option<(&'static str,&'static str)>,
+            pub camel_case_types: bool,
+            pub raw_strings: bool,
+            pub backtick_strings: bool,
+            pub case_insensitive_keywords: bool
         }
 
         impl $Name {
@@ -73,22 +136,53 @@ macro_rules! syntax_struct {
                 Self {
                     extensions: &$ext,
                     file_type: $type,
-                    comment_start:$start,
-                    multiline_comment: $ml_comment
+                    comment_start: &$start,
+                    doc_comments: &$doc,
+                    multiline_comment: $ml_comment,
+                    camel_case_types: $camel,
+                    raw_strings: $raw,
+                    backtick_strings: $backtick,
+                    case_insensitive_keywords: $case_insensitive
                 }
             }
         }
 
+        impl Default for $Name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
         impl SyntaxHighlight for $Name {
 
-            fn comment_start(&self) -> &str {
+            fn comment_start(&self) -> &[&str] {
                 self.comment_start
             }
 
+            fn doc_comments(&self) -> &[&str] {
+                self.doc_comments
+            }
+
             fn multiline_comment(&self) -> Option<(&str, &str)> {
                 self.multiline_comment
             }
 
+            fn camel_case_types(&self) -> bool {
+                self.camel_case_types
+            }
+
+            fn raw_strings(&self) -> bool {
+                self.raw_strings
+            }
+
+            fn backtick_strings(&self) -> bool {
+                self.backtick_strings
+            }
+
+            fn case_insensitive_keywords(&self) -> bool {
+                self.case_insensitive_keywords
+            }
+
             fn extensions(&self) -> &[&str] {
                 self.extensions
             }
@@ -97,20 +191,9 @@ macro_rules! syntax_struct {
                 self.file_type
             }
 
-            fn syntax_color(&self, highlight_type: &HighlightType) -> Color {
-                match highlight_type {
-                    HighlightType::Normal => Color::Reset,
-                    HighlightType::Number => Color::Cyan,
-                    HighlightType::SearchMatch => Color::Blue,
-                    HighlightType::String => Color::Green,
-                    HighlightType::CharLiteral => Color::DarkGreen,
-                    HighlightType::Comment | HighlightType::MultilineComment => Color::DarkGrey,
-                    HighlightType::Other(color) => *color
-                }
-            }
-
-            fn update_syntax(&self, at: usize, editor_rows: &mut Vec<Row>) {
+            fn update_row_syntax(&self, at: usize, editor_rows: &mut [Row]) -> bool {
                 let mut in_comment = at > 0 && editor_rows[at - 1].is_comment; // add line
+                let mut in_doc_comment = in_comment && editor_rows[at - 1].is_doc_comment;
                 let current_row = &mut editor_rows[at];
                 macro_rules! add {
                     ($h:expr) => {
@@ -122,31 +205,55 @@ macro_rules! syntax_struct {
                 let mut i = 0;
                 let mut previous_separator = true;
                 let mut in_string: Option<char> = None;
-                let comment_start = self.comment_start().as_bytes();
-                while i < render.len() {
+                // A doc prefix followed by another `/` or `*` (as in `////` or `/**/`) is a
+                // plain comment.
+                let is_doc_comment = |i: usize| {
+                    self.doc_comments().iter().any(|doc| {
+                        render[i..].starts_with(doc.as_bytes())
+                            && !matches!(render.get(i + doc.len()), Some(b'/' | b'*'))
+                    })
+                };
+                // Past this, the rest of a huge row (such as minified code) stays plain so
+                // that editing it doesn't re-highlight megabytes on every key.
+                let highlighted = cmp::min(render.len(), MAX_HIGHLIGHT_LENGTH);
+                while i < highlighted {
                     let c = render[i] as char;
                     let previous_highlight = if i > 0 {
                         current_row.highlight[i - 1]
                     } else {
                         HighlightType::Normal
                     };
-                    if in_string.is_none() && !comment_start.is_empty() && !in_comment { // modify
-                        let end = i + comment_start.len();
-                        if render[i..cmp::min(end, render.len())] == *comment_start {
-                            (i..render.len()).for_each(|_| add!(HighlightType::Comment));
+                    if in_string.is_none() && !in_comment { // modify
+                        let is_comment_start = self
+                            .comment_start()
+                            .iter()
+                            .any(|start| render[i..].starts_with(start.as_bytes()));
+                        if is_comment_start {
+                            let highlight = if is_doc_comment(i) {
+                                HighlightType::DocComment
+                            } else {
+                                HighlightType::Comment
+                            };
+                            (i..render.len()).for_each(|_| add!(highlight));
                             break;
                         }
                     }
                     if let Some(val) = $ml_comment {
                         if in_string.is_none() {
                             if in_comment {
-                                add!(HighlightType::MultilineComment);
+                                let highlight = if in_doc_comment {
+                                    HighlightType::DocComment
+                                } else {
+                                    HighlightType::MultilineComment
+                                };
+                                add!(highlight);
                                 let end = i + val.1.len();
                                 if render[i..cmp::min(render.len(),end)] == *val.1.as_bytes() {
-                                    (0..val.1.len().saturating_sub(1)).for_each(|_| add!(HighlightType::MultilineComment));
+                                    (0..val.1.len().saturating_sub(1)).for_each(|_| add!(highlight));
                                     i = end;
                                     previous_separator = true;
                                     in_comment = false;
+                                    in_doc_comment = false;
                                     continue
                                 } else {
                                     i+=1;
@@ -155,7 +262,13 @@ macro_rules! syntax_struct {
                             } else {
                                 let end = i + val.0.len();
                                 if render[i..cmp::min(render.len(),end)] == *val.0.as_bytes() {
-                                    (i..end).for_each(|_| add!(HighlightType::MultilineComment));
+                                    in_doc_comment = is_doc_comment(i);
+                                    let highlight = if in_doc_comment {
+                                        HighlightType::DocComment
+                                    } else {
+                                        HighlightType::MultilineComment
+                                    };
+                                    (i..end).for_each(|_| add!(highlight));
                                     i+= val.0.len();
                                     in_comment = true;
                                     continue
@@ -164,30 +277,56 @@ macro_rules! syntax_struct {
                         }
                     }
                     if let Some(val) = in_string {
-                        add! {
-                            if val == '"' { HighlightType::String } else { HighlightType::CharLiteral }
-                        }
                         if c == '\\' && i + 1 < render.len() {
-                            add! {
-                                if val == '"' { HighlightType::String } else { HighlightType::CharLiteral }
-                            }
-                            i += 2;
+                            // `\x41` and `\u{..}` escapes run past the character after the backslash.
+                            let len = match render[i + 1] {
+                                b'x' => 4,
+                                b'u' if render.get(i + 2) == Some(&b'{') => render[i..]
+                                    .iter()
+                                    .position(|b| *b == b'}')
+                                    .map_or(2, |end| end + 1),
+                                _ => 2,
+                            };
+                            let len = cmp::min(len, render.len() - i);
+                            (0..len).for_each(|_| add!(HighlightType::Escape));
+                            i += len;
                             continue
                         }
+                        add! {
+                            if val == '\'' { HighlightType::CharLiteral } else { HighlightType::String }
+                        }
                         if val == c {
                             in_string = None;
                         }
                         i += 1;
                         previous_separator = true;
                         continue;
-                    } else if c == '"' || c == '\'' {
+                    } else if c == '"' || c == '\'' || (c == '`' && self.backtick_strings()) {
                         in_string = Some(c);
                         add! {
-                            if c == '"' { HighlightType::String } else { HighlightType::CharLiteral }
+                            if c == '\'' { HighlightType::CharLiteral } else { HighlightType::String }
                         }
                         i += 1;
                         continue;
                     }
+                    if self.raw_strings() && previous_separator && c == 'r' {
+                        let hashes = render[i + 1..].iter().take_while(|b| **b == b'#').count();
+                        if render.get(i + 1 + hashes) == Some(&b'"') {
+                            // The string only ends at a quote followed by as many hashes.
+                            let body = i + 2 + hashes;
+                            let end = (body..render.len())
+                                .find(|j| {
+                                    render[*j] == b'"'
+                                        && render[j + 1..].iter().take_while(|b| **b == b'#').count()
+                                            >= hashes
+                                })
+                                .map_or(render.len(), |j| j + 1 + hashes);
+                            (i..end).for_each(|_| add!(HighlightType::String));
+                            i = end;
+                            previous_separator = true;
+                            continue;
+                        }
+                    }
                     if (c.is_digit(10)
                         && (previous_separator
                             || matches!(previous_highlight, HighlightType::Number)))
@@ -206,7 +345,12 @@ macro_rules! syntax_struct {
                                     .get(end)
                                     .map(|c| self.is_separator(*c as char))
                                     .unwrap_or(end == render.len());
-                                if is_end_or_sep && render[i..end] == *$words.as_bytes() {
+                                let is_keyword = is_end_or_sep && if self.case_insensitive_keywords() {
+                                    render[i..end].eq_ignore_ascii_case($words.as_bytes())
+                                } else {
+                                    render[i..end] == *$words.as_bytes()
+                                };
+                                if is_keyword {
                                     (i..end).for_each(|_| add!(HighlightType::Other($color)));
                                     i += $words.len();
                                     previous_separator = false;
@@ -214,17 +358,41 @@ macro_rules! syntax_struct {
                                 }
                             )*
                         )*
+                        if c.is_ascii_alphabetic() || c == '_' {
+                            let end = render[i..]
+                                .iter()
+                                .position(|b| !(b.is_ascii_alphanumeric() || *b == b'_'))
+                                .map_or(render.len(), |len| i + len);
+                            if self.camel_case_types() && c.is_ascii_uppercase() {
+                                (i..end).for_each(|_| add!(HighlightType::Type));
+                                i = end;
+                                previous_separator = false;
+                                continue;
+                            }
+                            let is_call = match render.get(end) {
+                                Some(b'(') => true,
+                                Some(b'!') => render.get(end + 1) != Some(&b'='),
+                                _ => false,
+                            };
+                            if is_call {
+                                (i..end).for_each(|_| add!(HighlightType::Function));
+                                i = end;
+                                previous_separator = false;
+                                continue;
+                            }
+                        }
                     }
                     add!(HighlightType::Normal);
                     previous_separator = self.is_separator(c);
                     i += 1;
                 }
+                current_row.highlight.resize(render.len(), HighlightType::Normal);
                 assert_eq!(current_row.render.len(), current_row.highlight.len());
-                let changed = current_row.is_comment != in_comment;
+                let changed = current_row.is_comment != in_comment
+                    || current_row.is_doc_comment != in_doc_comment;
                 current_row.is_comment = in_comment;
-                if (changed && at + 1 < editor_rows.len()) {
-                    self.update_syntax(at+1,editor_rows)
-                }
+                current_row.is_doc_comment = in_doc_comment;
+                changed
             }
         }
     };
@@ -234,7 +402,8 @@ syntax_struct! {
     struct RustHighlight {
         extensions:["rs"],
         file_type:"rust",
-        comment_start:"//",
+        comment_start:["//"],
+        doc_comments:["///", "//!", "/**", "/*!"],
         keywords : {
             [Color::Yellow;
                 "mod","unsafe","extern","crate","use","type","struct","enum","union","const","static",
@@ -245,6 +414,629 @@ syntax_struct! {
                 "char","str","bool"
             ]
         },
-        multiline_comment: Some(("/*", "*/"))
+        multiline_comment: Some(("/*", "*/")),
+        camel_case_types: true,
+        raw_strings: true,
+        backtick_strings: false,
+        case_insensitive_keywords: false
+    }
+}
+
+syntax_struct! {
+    struct GoHighlight {
+        extensions:["go"],
+        file_type:"go",
+        comment_start:["//"],
+        doc_comments:[],
+        keywords : {
+            [Color::Yellow;
+                "break","case","chan","const","continue","default","defer","else","fallthrough",
+                "for","func","go","goto","if","import","interface","map","package","range",
+                "return","select","struct","switch","type","var","true","false","nil","iota"
+            ],
+            [Color::Magenta; "bool","byte","complex64","complex128","error","float32","float64",
+                "int","int8","int16","int32","int64","rune","string","uint","uint8","uint16",
+                "uint32","uint64","uintptr","any"
+            ]
+        },
+        multiline_comment: Some(("/*", "*/")),
+        camel_case_types: false,
+        raw_strings: false,
+        backtick_strings: true,
+        case_insensitive_keywords: false
+    }
+}
+
+/// Highlights YAML by its line structure: `key:` prefixes, `- ` list markers, quoted
+/// strings, `&anchor`/`*alias` names and `#` comments.
+pub struct YamlHighlight;
+
+impl YamlHighlight {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Returns the end of the `key` in a `key: value` line whose key starts at `start`.
+    fn key_end(render: &[u8], start: usize) -> Option<usize> {
+        if matches!(
+            render.get(start),
+            None | Some(b'#' | b'"' | b'\'' | b'&' | b'*')
+        ) {
+            return None;
+        }
+        let mut i = start;
+        while i < render.len() {
+            match render[i] {
+                b':' if matches!(render.get(i + 1), None | Some(b' ')) => return Some(i),
+                b'#' if render[i - 1] == b' ' => return None,
+                _ => i += 1,
+            }
+        }
+        None
+    }
+}
+
+impl Default for YamlHighlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyntaxHighlight for YamlHighlight {
+    fn extensions(&self) -> &[&str] {
+        &["yaml", "yml"]
+    }
+
+    fn file_type(&self) -> &str {
+        "yaml"
+    }
+
+    fn comment_start(&self) -> &[&str] {
+        &["#"]
+    }
+
+    fn update_row_syntax(&self, at: usize, editor_rows: &mut [Row]) -> bool {
+        let row = &mut editor_rows[at];
+        let render = row.render.as_bytes();
+        row.highlight = vec![HighlightType::Normal; render.len()];
+        let mut i = render.iter().take_while(|b| **b == b' ').count();
+        while render.get(i) == Some(&b'-') && matches!(render.get(i + 1), None | Some(b' ')) {
+            row.highlight[i] = HighlightType::Other(Color::DarkMagenta);
+            i += 1;
+            i += render[i..].iter().take_while(|b| **b == b' ').count();
+        }
+        if let Some(end) = Self::key_end(render, i) {
+            row.highlight[i..end].fill(HighlightType::Other(Color::Yellow));
+            i = end + 1;
+        }
+        let mut in_string: Option<u8> = None;
+        while i < render.len() {
+            let c = render[i];
+            let after_space = i == 0 || matches!(render[i - 1], b' ' | b'[' | b'{' | b',');
+            if let Some(quote) = in_string {
+                row.highlight[i] = HighlightType::String;
+                if c == quote {
+                    in_string = None;
+                }
+            } else if c == b'#' && (i == 0 || render[i - 1] == b' ') {
+                row.highlight[i..].fill(HighlightType::Comment);
+                break;
+            } else if (c == b'"' || c == b'\'') && after_space {
+                row.highlight[i] = HighlightType::String;
+                in_string = Some(c);
+            } else if (c == b'&' || c == b'*') && after_space {
+                let end = render[i..]
+                    .iter()
+                    .position(|b| matches!(b, b' ' | b',' | b']' | b'}'))
+                    .map_or(render.len(), |len| i + len);
+                row.highlight[i..end].fill(HighlightType::Other(Color::Magenta));
+                i = end;
+                continue;
+            }
+            i += 1;
+        }
+        let changed = row.is_comment;
+        row.is_comment = false;
+        changed
+    }
+}
+
+syntax_struct! {
+    struct JsHighlight {
+        extensions:["js", "mjs", "cjs", "jsx", "ts", "tsx"],
+        file_type:"javascript",
+        comment_start:["//"],
+        doc_comments:["/**"],
+        keywords : {
+            [Color::Yellow;
+                "function","const","let","var","return","if","else","for","while","do","switch",
+                "case","default","break","continue","class","extends","new","this","super","async",
+                "await","yield","import","export","from","as","try","catch","finally","throw",
+                "typeof","instanceof","in","of","delete","void","true","false","null","undefined"
+            ],
+            [Color::Magenta; "interface","type","enum","implements","private","public",
+                "protected","readonly","number","string","boolean","any","unknown","never"
+            ]
+        },
+        multiline_comment: Some(("/*", "*/")),
+        camel_case_types: true,
+        raw_strings: false,
+        backtick_strings: true,
+        case_insensitive_keywords: false
+    }
+}
+
+/// Highlights CSS: selectors, property names and their values, `#hex` colors and
+/// `/* */` comments. Each `;`, `{` or `}` separated part of a line is classified on
+/// its own, so a declaration split over several lines is only partly recognized.
+pub struct CssHighlight;
+
+impl CssHighlight {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Colors the non-blank code (not comment or string) in `range`.
+    fn color_code(
+        render: &[u8],
+        highlight: &mut [HighlightType],
+        range: Range<usize>,
+        color: Color,
+    ) {
+        for i in range {
+            if matches!(highlight[i], HighlightType::Normal) && render[i] != b' ' {
+                highlight[i] = HighlightType::Other(color);
+            }
+        }
+    }
+
+    fn highlight_declaration(render: &[u8], highlight: &mut [HighlightType], start: usize) {
+        let mut i = start;
+        while i < render.len() {
+            let c = render[i];
+            if !matches!(highlight[i], HighlightType::Normal) || c == b' ' {
+                i += 1;
+                continue;
+            }
+            let end = (i..render.len())
+                .find(|j| {
+                    !matches!(highlight[*j], HighlightType::Normal)
+                        || matches!(render[*j], b' ' | b',' | b'(' | b')' | b'/')
+                })
+                .map_or(render.len(), |end| cmp::max(end, i + 1));
+            let color = if c == b'#' {
+                HighlightType::Other(Color::Red)
+            } else if c.is_ascii_digit()
+                || (c == b'.' && render.get(i + 1).is_some_and(u8::is_ascii_digit))
+            {
+                HighlightType::Number
+            } else {
+                HighlightType::Other(Color::DarkGreen)
+            };
+            highlight[i..end].fill(color);
+            i = end;
+        }
+    }
+}
+
+impl Default for CssHighlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyntaxHighlight for CssHighlight {
+    fn extensions(&self) -> &[&str] {
+        &["css"]
+    }
+
+    fn file_type(&self) -> &str {
+        "css"
+    }
+
+    fn comment_start(&self) -> &[&str] {
+        &[]
+    }
+
+    fn multiline_comment(&self) -> Option<(&str, &str)> {
+        Some(("/*", "*/"))
+    }
+
+    fn update_row_syntax(&self, at: usize, editor_rows: &mut [Row]) -> bool {
+        let mut in_comment = at > 0 && editor_rows[at - 1].is_comment;
+        let row = &mut editor_rows[at];
+        let render = row.render.as_bytes();
+        row.highlight = vec![HighlightType::Normal; render.len()];
+        // Comments and strings first, so the structure below only looks at code.
+        let mut in_string: Option<u8> = None;
+        let mut i = 0;
+        while i < render.len() {
+            if in_comment {
+                row.highlight[i] = HighlightType::MultilineComment;
+                if render[i..].starts_with(b"*/") {
+                    row.highlight[i + 1] = HighlightType::MultilineComment;
+                    in_comment = false;
+                    i += 1;
+                }
+            } else if let Some(quote) = in_string {
+                row.highlight[i] = HighlightType::String;
+                if render[i] == quote {
+                    in_string = None;
+                }
+            } else if render[i..].starts_with(b"/*") {
+                row.highlight[i] = HighlightType::MultilineComment;
+                in_comment = true;
+            } else if render[i] == b'"' || render[i] == b'\'' {
+                row.highlight[i] = HighlightType::String;
+                in_string = Some(render[i]);
+            }
+            i += 1;
+        }
+        let is_code =
+            |highlight: &[HighlightType], i: usize| matches!(highlight[i], HighlightType::Normal);
+        let mut start = 0;
+        while start < render.len() {
+            let end = (start..render.len())
+                .find(|i| is_code(&row.highlight, *i) && matches!(render[*i], b';' | b'{' | b'}'))
+                .unwrap_or(render.len());
+            let code = (start..end)
+                .filter(|i| is_code(&row.highlight, *i))
+                .map(|i| render[i])
+                .collect::<Vec<u8>>();
+            let code = String::from_utf8_lossy(&code);
+            let colon = (start..end).find(|i| is_code(&row.highlight, *i) && render[*i] == b':');
+            let is_selector = render.get(end) == Some(&b'{')
+                || (end == render.len() && code.trim_end().ends_with(','));
+            match colon {
+                Some(colon) if !is_selector => {
+                    Self::color_code(render, &mut row.highlight, start..colon, Color::Magenta);
+                    Self::highlight_declaration(
+                        &render[..end],
+                        &mut row.highlight[..end],
+                        colon + 1,
+                    );
+                }
+                _ if is_selector || (end == render.len() && !code.trim().is_empty()) => {
+                    Self::color_code(render, &mut row.highlight, start..end, Color::Yellow);
+                }
+                _ => {}
+            }
+            start = end + 1;
+        }
+        let changed = row.is_comment != in_comment;
+        row.is_comment = in_comment;
+        changed
+    }
+}
+
+/// Highlights HTML and XML tags, attribute names and values, `&entities;` and
+/// `<!-- -->` comments, leaving text content uncolored.
+pub struct HtmlHighlight;
+
+impl HtmlHighlight {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Highlights the tag starting with the `<` at `start`, returning where it ends.
+    fn highlight_tag(render: &[u8], highlight: &mut [HighlightType], start: usize) -> usize {
+        let is_name = |b: &u8| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b':' | b'.');
+        let name_end = render[start + 1..]
+            .iter()
+            .position(|b| !(is_name(b) || matches!(b, b'/' | b'!' | b'?')))
+            .map_or(render.len(), |len| start + 1 + len);
+        highlight[start..name_end].fill(HighlightType::Other(Color::Yellow));
+        let mut i = name_end;
+        while i < render.len() {
+            match render[i] {
+                b'>' => {
+                    highlight[i] = HighlightType::Other(Color::Yellow);
+                    return i + 1;
+                }
+                quote @ (b'"' | b'\'') => {
+                    let end = render[i + 1..]
+                        .iter()
+                        .position(|b| *b == quote)
+                        .map_or(render.len(), |len| i + len + 2);
+                    highlight[i..end].fill(HighlightType::String);
+                    i = end;
+                }
+                b'=' if render
+                    .get(i + 1)
+                    .is_some_and(|b| !matches!(b, b'"' | b'\'' | b' ' | b'>')) =>
+                {
+                    // An unquoted attribute value runs to the next space or the end of the tag.
+                    let end = render[i + 1..]
+                        .iter()
+                        .position(|b| matches!(b, b' ' | b'>'))
+                        .map_or(render.len(), |len| i + 1 + len);
+                    highlight[i + 1..end].fill(HighlightType::String);
+                    i = end;
+                }
+                b if is_name(&b) => {
+                    let end = render[i..]
+                        .iter()
+                        .position(|b| !is_name(b))
+                        .map_or(render.len(), |len| i + len);
+                    highlight[i..end].fill(HighlightType::Other(Color::Magenta));
+                    i = end;
+                }
+                _ => i += 1,
+            }
+        }
+        i
+    }
+}
+
+impl Default for HtmlHighlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyntaxHighlight for HtmlHighlight {
+    fn extensions(&self) -> &[&str] {
+        &["html", "htm", "xml", "svg"]
+    }
+
+    fn file_type(&self) -> &str {
+        "html"
+    }
+
+    fn comment_start(&self) -> &[&str] {
+        &[]
+    }
+
+    fn multiline_comment(&self) -> Option<(&str, &str)> {
+        Some(("<!--", "-->"))
+    }
+
+    fn update_row_syntax(&self, at: usize, editor_rows: &mut [Row]) -> bool {
+        let mut in_comment = at > 0 && editor_rows[at - 1].is_comment;
+        let row = &mut editor_rows[at];
+        let render = row.render.as_bytes();
+        row.highlight = vec![HighlightType::Normal; render.len()];
+        let mut i = 0;
+        while i < render.len() {
+            if in_comment {
+                if render[i..].starts_with(b"-->") {
+                    row.highlight[i..i + 3].fill(HighlightType::MultilineComment);
+                    in_comment = false;
+                    i += 3;
+                } else {
+                    row.highlight[i] = HighlightType::MultilineComment;
+                    i += 1;
+                }
+            } else if render[i..].starts_with(b"<!--") {
+                row.highlight[i..i + 4].fill(HighlightType::MultilineComment);
+                in_comment = true;
+                i += 4;
+            } else if render[i] == b'<'
+                && render
+                    .get(i + 1)
+                    .is_some_and(|b| b.is_ascii_alphabetic() || matches!(b, b'/' | b'!' | b'?'))
+            {
+                i = Self::highlight_tag(render, &mut row.highlight, i);
+            } else if render[i] == b'&' {
+                let end = render[i + 1..]
+                    .iter()
+                    .position(|b| !(b.is_ascii_alphanumeric() || *b == b'#'))
+                    .map(|len| i + 1 + len)
+                    .filter(|end| *end > i + 1 && render[*end] == b';');
+                match end {
+                    Some(end) => {
+                        row.highlight[i..=end].fill(HighlightType::Escape);
+                        i = end + 1;
+                    }
+                    None => i += 1,
+                }
+            } else {
+                i += 1;
+            }
+        }
+        let changed = row.is_comment != in_comment;
+        row.is_comment = in_comment;
+        changed
+    }
+}
+
+syntax_struct! {
+    struct SqlHighlight {
+        extensions:["sql"],
+        file_type:"sql",
+        comment_start:["--", "#"],
+        doc_comments:[],
+        keywords : {
+            [Color::Yellow;
+                "select","from","where","insert","into","values","update","set","delete","join",
+                "inner","left","right","outer","full","cross","on","using","group","by","order",
+                "having","limit","offset","as","distinct","union","all","and","or","not","in",
+                "is","null","like","between","exists","case","when","then","else","end","create",
+                "alter","drop","table","index","view","primary","key","foreign","references",
+                "default","unique","constraint","begin","commit","rollback","true","false","asc",
+                "desc","with","returning"
+            ],
+            [Color::Magenta; "int","integer","bigint","smallint","serial","real","float",
+                "double","decimal","numeric","char","varchar","text","boolean","date","time",
+                "timestamp","blob"
+            ]
+        },
+        multiline_comment: Some(("/*", "*/")),
+        camel_case_types: false,
+        raw_strings: false,
+        backtick_strings: false,
+        case_insensitive_keywords: true
+    }
+}
+
+/// Highlights INI-style config files: `[section]` headers, keys and values of
+/// `key = value` lines, and `;` or `#` comment lines.
+pub struct IniHighlight;
+
+impl IniHighlight {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for IniHighlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyntaxHighlight for IniHighlight {
+    fn extensions(&self) -> &[&str] {
+        &["ini", "conf", "cfg"]
+    }
+
+    fn file_type(&self) -> &str {
+        "ini"
+    }
+
+    fn comment_start(&self) -> &[&str] {
+        &[";", "#"]
+    }
+
+    fn update_row_syntax(&self, at: usize, editor_rows: &mut [Row]) -> bool {
+        let row = &mut editor_rows[at];
+        let render = row.render.as_bytes();
+        row.highlight = vec![HighlightType::Normal; render.len()];
+        let start = render
+            .iter()
+            .take_while(|b| b.is_ascii_whitespace())
+            .count();
+        match render.get(start) {
+            Some(b';' | b'#') => row.highlight[start..].fill(HighlightType::Comment),
+            Some(b'[') => {
+                let end = render
+                    .iter()
+                    .rposition(|b| *b == b']')
+                    .map_or(render.len(), |end| end + 1);
+                row.highlight[start..end].fill(HighlightType::Other(Color::Yellow));
+            }
+            Some(_) => {
+                if let Some(equals) = render.iter().position(|b| *b == b'=') {
+                    row.highlight[start..equals].fill(HighlightType::Other(Color::Magenta));
+                    let value = equals
+                        + 1
+                        + render[equals + 1..]
+                            .iter()
+                            .take_while(|b| b.is_ascii_whitespace())
+                            .count();
+                    let highlight = match render.get(value) {
+                        Some(b'"' | b'\'') => HighlightType::String,
+                        Some(b) if b.is_ascii_digit() => HighlightType::Number,
+                        _ => HighlightType::Other(Color::DarkGreen),
+                    };
+                    row.highlight[value..].fill(highlight);
+                }
+            }
+            None => {}
+        }
+        false
+    }
+}
+
+/// Reports a file type for text files without coloring anything, not even numbers.
+pub struct PlainTextHighlight;
+
+impl PlainTextHighlight {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for PlainTextHighlight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyntaxHighlight for PlainTextHighlight {
+    fn extensions(&self) -> &[&str] {
+        &["txt", "text"]
+    }
+
+    fn file_type(&self) -> &str {
+        "text"
+    }
+
+    fn comment_start(&self) -> &[&str] {
+        &[]
+    }
+
+    fn update_row_syntax(&self, at: usize, editor_rows: &mut [Row]) -> bool {
+        let row = &mut editor_rows[at];
+        row.highlight = vec![HighlightType::Normal; row.render.len()];
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor_rows::EditorRows;
+
+    fn rows_with(syntax: Box<dyn SyntaxHighlight>, contents: &str) -> EditorRows {
+        EditorRows::from_str(contents, &mut Some(syntax))
+    }
+
+    #[test]
+    fn stops_rehighlighting_when_comment_state_is_unchanged() {
+        let syntax = RustHighlight::new();
+        let mut rows = rows_with(Box::new(RustHighlight::new()), "let a = 1;\nlet b = 2;\n");
+        // Left stale on purpose: re-highlighting row 0 must not reach row 1.
+        rows.row_contents[1].highlight.clear();
+        syntax.update_syntax(0, &mut rows.row_contents);
+        assert_eq!(rows.row_contents[0].highlight.len(), 10);
+        assert!(rows.row_contents[1].highlight.is_empty());
+    }
+
+    #[test]
+    fn rehighlights_rows_below_an_opened_comment() {
+        let syntax = RustHighlight::new();
+        let mut rows = rows_with(Box::new(RustHighlight::new()), "x\nlet b;\nlet c;\n");
+        let row = rows.get_editor_row_mut(0);
+        row.row_content = "/* x".into();
+        EditorRows::render_row(row);
+        syntax.update_syntax(0, &mut rows.row_contents);
+        assert_eq!(rows.row_contents[2].highlight.len(), 6);
+        assert!(rows.row_contents[2]
+            .highlight
+            .iter()
+            .all(|highlight| *highlight == HighlightType::MultilineComment));
+    }
+
+    #[test]
+    fn matches_keywords_regardless_of_case_when_the_syntax_asks() {
+        let rows = rows_with(Box::new(SqlHighlight::new()), "SeLeCt x FROM t;\n");
+        let highlight = &rows.row_contents[0].highlight;
+        let keyword = HighlightType::Other(Color::Yellow);
+        assert_eq!(highlight[..6], [keyword; 6]);
+        assert_eq!(highlight[6], HighlightType::Normal);
+        assert_eq!(highlight[9..13], [keyword; 4]);
+        let rows = rows_with(Box::new(RustHighlight::new()), "LET x;\n");
+        assert_ne!(rows.row_contents[0].highlight[0], keyword);
+    }
+
+    #[test]
+    fn starts_a_comment_at_any_of_the_prefixes() {
+        let rows = rows_with(
+            Box::new(SqlHighlight::new()),
+            "x -- dashes\ny # hash\nz - 1\n",
+        );
+        assert_eq!(
+            rows.row_contents[0].highlight[2..],
+            [HighlightType::Comment; 9]
+        );
+        assert_eq!(
+            rows.row_contents[1].highlight[2..],
+            [HighlightType::Comment; 6]
+        );
+        assert!(!rows.row_contents[2]
+            .highlight
+            .contains(&HighlightType::Comment));
+        assert_eq!(SqlHighlight::new().comment_start()[0], "--");
     }
 }
@@ -0,0 +1,32 @@
+use std::{
+    ffi::CString,
+    mem,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Formats the current local time with the `strftime` pattern `format`.
+pub fn now(format: &str) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs()) as libc::time_t;
+    let format = match CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+    let mut buffer = [0u8; 128];
+    // SAFETY: `tm` is plain data filled in by `localtime_r`, and `strftime` writes at
+    // most `buffer.len()` bytes into `buffer`.
+    let len = unsafe {
+        let mut tm: libc::tm = mem::zeroed();
+        if libc::localtime_r(&seconds, &mut tm).is_null() {
+            return String::new();
+        }
+        libc::strftime(
+            buffer.as_mut_ptr() as *mut libc::c_char,
+            buffer.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+    String::from_utf8_lossy(&buffer[..len]).into_owned()
+}